@@ -0,0 +1,170 @@
+//! A scripted stand-in for the simulator image, used with the `process` backend.
+//!
+//! It speaks just enough of the simulator protocol for the driver: reads the initial input,
+//! sends the turn number to the player(s) each turn, reads one reply line per player and
+//! logs `TURN`/`COINS`/`DESTRUCTION` (normal) or `TURN`/`SCORE`/`DELIMITER` (pvp) to stderr.
+//!
+//! Extra flags (put them in the `process_images` command):
+//! `--fail-at-turn=N` exits with an error at turn N, `--hang` never finishes.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    process::exit,
+};
+
+struct Options {
+    pvp: bool,
+    fail_at_turn: Option<u32>,
+    hang: bool,
+    fifos: Vec<(String, String)>,
+}
+
+impl Options {
+    fn parse() -> Options {
+        let mut options = Options {
+            pvp: false,
+            fail_at_turn: None,
+            hang: false,
+            fifos: vec![],
+        };
+        for arg in std::env::args().skip(1) {
+            if let Some(kind) = arg.strip_prefix("--type=") {
+                options.pvp = kind == "PvP";
+            } else if let Some(turn) = arg.strip_prefix("--fail-at-turn=") {
+                options.fail_at_turn = turn.parse().ok();
+            } else if arg == "--hang" {
+                options.hang = true;
+            } else if let Some((name, path)) = arg.split_once('=') {
+                options.fifos.push((name.to_owned(), path.to_owned()));
+            }
+        }
+        options
+    }
+
+    fn fifo(&self, name: &str) -> String {
+        match self.fifos.iter().find(|(n, _)| n == name) {
+            Some((_, path)) => path.to_owned(),
+            None => fail(&format!("missing {name}=<path> argument")),
+        }
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{message}");
+    exit(1);
+}
+
+fn read_line(reader: &mut impl BufRead, what: &str) -> String {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => fail(&format!("unexpected end of input while reading {what}")),
+        Ok(_) => line.trim().to_owned(),
+        Err(e) => fail(&format!("couldnt read {what}: {e}")),
+    }
+}
+
+fn read_count(reader: &mut impl BufRead, what: &str) -> Vec<u32> {
+    let line = read_line(reader, what);
+    line.split_whitespace()
+        .map(|x| x.parse())
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|_| fail(&format!("malformed {what}: {line:?}")))
+}
+
+/// Reads `turns coins`, the attackers and the defenders and returns `(turns, coins)`.
+fn read_parameters(reader: &mut impl BufRead) -> (u32, u32) {
+    let header = read_count(reader, "turns and coins");
+    let (turns, coins) = match header[..] {
+        [turns, coins] => (turns, coins),
+        _ => fail("expected `turns coins` as the first line"),
+    };
+    for troops in ["attackers", "defenders"] {
+        let count = read_count(reader, troops).first().copied().unwrap_or(0);
+        for _ in 0..count {
+            read_line(reader, troops);
+        }
+    }
+    (turns, coins)
+}
+
+fn send_turn(writer: &mut impl Write, turn: u32) {
+    if let Err(e) = writeln!(writer, "{turn}").and_then(|_| writer.flush()) {
+        fail(&format!("couldnt talk to the player: {e}"));
+    }
+}
+
+fn check_turn(options: &Options, turn: u32) {
+    if options.fail_at_turn == Some(turn) {
+        fail(&format!("simulator crashed at turn {turn}"));
+    }
+    if options.hang {
+        loop {
+            std::thread::park();
+        }
+    }
+}
+
+fn normal_game(options: &Options) {
+    let mut input = BufReader::new(io::stdin());
+    let mut output = io::stdout();
+    let (turns, mut coins) = read_parameters(&mut input);
+    let size = read_count(&mut input, "map size")
+        .first()
+        .copied()
+        .unwrap_or(0);
+    for _ in 0..size {
+        read_line(&mut input, "map");
+    }
+
+    for turn in 1..=turns {
+        check_turn(options, turn);
+        send_turn(&mut output, turn);
+        read_line(&mut input, "the player's move");
+        coins = coins.saturating_sub(1);
+        eprintln!("TURN, {turn}");
+        eprintln!("COINS, {coins}");
+        eprintln!(
+            "DESTRUCTION, {:.2}%",
+            f64::from(turn) * 100.0 / f64::from(turns)
+        );
+    }
+}
+
+fn pvp_game(options: &Options) {
+    let mut input = BufReader::new(io::stdin());
+    let (turns, _) = read_parameters(&mut input);
+
+    let open = |name: &str, write: bool| -> File {
+        OpenOptions::new()
+            .read(!write)
+            .write(write)
+            .open(options.fifo(name))
+            .unwrap_or_else(|e| fail(&format!("couldnt open {name}: {e}")))
+    };
+    let mut p1_in = BufReader::new(open("p1_in", false));
+    let mut p1_out = open("p1_out", true);
+    let mut p2_in = BufReader::new(open("p2_in", false));
+    let mut p2_out = open("p2_out", true);
+
+    let (mut p1_log, mut p2_log) = (String::new(), String::new());
+    for turn in 1..=turns {
+        check_turn(options, turn);
+        send_turn(&mut p1_out, turn);
+        send_turn(&mut p2_out, turn);
+        read_line(&mut p1_in, "player 1's move");
+        read_line(&mut p2_in, "player 2's move");
+        p1_log.push_str(&format!("TURN, {turn}\n"));
+        p2_log.push_str(&format!("TURN, {turn}\n"));
+    }
+    eprint!("{p1_log}SCORE, {turns}\nDELIMITER\n{p2_log}SCORE, 0\n");
+}
+
+fn main() {
+    let options = Options::parse();
+    if options.pvp {
+        pvp_game(&options);
+    } else {
+        normal_game(&options);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{never, select, Receiver, Sender};
+
+use crate::{
+    create_stage_response,
+    error::SimulatorError,
+    request::GameRequest,
+    response::{GameProgress, GameStage, GameStatus},
+    workers::Workers,
+};
+
+/// Where game requests come from, e.g. the request queues, the HTTP API or a channel.
+pub trait RequestSource {
+    /// Hands the requests to `workers` until `stop` is disconnected, then lets the running
+    /// games finish.
+    fn run(self: Box<Self>, workers: Workers, stop: Receiver<()>) -> Result<(), SimulatorError>;
+}
+
+/// How a request is settled with the source it came from.
+pub enum Action {
+    Ack,
+    Requeue,
+    Retry(String),
+    DeadLetter(String),
+}
+
+/// Settles one request with the source it came from, e.g. a delivery on the request queues.
+pub trait Settler: Send {
+    fn settle(self: Box<Self>, action: Action);
+}
+
+/// How a request is settled once its result is published.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Ack,
+    /// Gave up on the request after it kept failing, with the last error.
+    DeadLetter(String),
+}
+
+/// Settles one request from the thread that handled it. A source that acknowledges requests
+/// keeps them until then, so a game that never finishes is played again.
+pub struct Ticket {
+    attempt: u32,
+    settler: Option<Box<dyn Settler>>,
+    /// Set once the request was put back without waiting for the game any longer.
+    abandoned: Arc<AtomicBool>,
+}
+
+impl Ticket {
+    pub fn new(attempt: u32, settler: Box<dyn Settler>, abandoned: Arc<AtomicBool>) -> Self {
+        Ticket {
+            attempt,
+            settler: Some(settler),
+            abandoned,
+        }
+    }
+
+    /// A ticket for a request that didn't come from a queue, with nothing to settle.
+    pub fn detached() -> Self {
+        Ticket {
+            attempt: 1,
+            settler: None,
+            abandoned: Arc::default(),
+        }
+    }
+
+    /// Whether the request was put back while the game was still being played, so its result
+    /// must not be published anymore.
+    pub fn is_abandoned(&self) -> bool {
+        self.abandoned.load(Ordering::Acquire)
+    }
+
+    /// Whether the request can be put back to be played again.
+    pub fn can_retry(&self) -> bool {
+        self.settler.is_some()
+    }
+
+    /// Which attempt at the request this is, starting at 1.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn ack(self) {
+        self.settle(Action::Ack)
+    }
+
+    /// Puts the request back at the end of its queue with the attempt and `error` recorded.
+    pub fn retry(self, error: String) {
+        self.settle(Action::Retry(error))
+    }
+
+    /// Moves the request to the dead-letter queue with the attempt and `error` recorded.
+    pub fn dead_letter(self, error: String) {
+        self.settle(Action::DeadLetter(error))
+    }
+
+    pub fn conclude(self, outcome: Outcome) {
+        match outcome {
+            Outcome::Ack => self.ack(),
+            Outcome::DeadLetter(error) => self.dead_letter(error),
+        }
+    }
+
+    fn settle(mut self, action: Action) {
+        self.send(action);
+    }
+
+    fn send(&mut self, action: Action) {
+        if let Some(settler) = self.settler.take() {
+            settler.settle(action);
+        }
+    }
+}
+
+impl Drop for Ticket {
+    /// A ticket dropped without being settled (e.g. a panicking worker) puts the game back.
+    fn drop(&mut self) {
+        self.send(Action::Requeue);
+    }
+}
+
+/// A game request along with where to publish its statuses and the ticket to acknowledge it
+/// once its result is published.
+pub struct Job {
+    pub request: GameRequest,
+    pub ticket: Ticket,
+    pub sink: Arc<dyn ResponseSink>,
+}
+
+/// Where the statuses of a game go, e.g. the response queue or the HTTP API's store.
+pub trait ResponseSink: Send + Sync {
+    fn publish(&self, response: GameStatus) -> Result<(), SimulatorError>;
+
+    /// Publishes the result of the game `ticket` is for, settling the ticket with `outcome` once
+    /// the result is delivered. Sinks that buffer results hold on to the ticket until then, so a
+    /// result lost on the way gets the request played again. The ticket is handed back when the
+    /// result is refused.
+    fn publish_result(
+        &self,
+        response: GameStatus,
+        ticket: Ticket,
+        outcome: Outcome,
+    ) -> Result<(), (SimulatorError, Ticket)> {
+        match self.publish(response) {
+            Ok(()) => {
+                ticket.conclude(outcome);
+                Ok(())
+            }
+            Err(e) => Err((e, ticket)),
+        }
+    }
+
+    /// Publishes how far a running game got. Sinks without anywhere to put it drop it.
+    fn progress(&self, _progress: GameProgress) -> Result<(), SimulatorError> {
+        Ok(())
+    }
+}
+
+/// What a game reports while it is being played, published to its sink if it has one.
+#[derive(Clone, Default)]
+pub struct Report {
+    game_id: String,
+    sink: Option<Arc<dyn ResponseSink>>,
+}
+
+impl Report {
+    pub fn new(game_id: String, sink: Arc<dyn ResponseSink>) -> Self {
+        Report {
+            game_id,
+            sink: Some(sink),
+        }
+    }
+
+    pub fn stage(&self, stage: GameStage) {
+        if let Some(sink) = &self.sink {
+            let game_id = &self.game_id;
+            if let Err(e) = sink.publish(create_stage_response(game_id, stage)) {
+                log::error!("Unable to publish that {game_id} is {stage:?}: {e:?}");
+            }
+        }
+    }
+
+    pub fn progress(&self, progress: GameProgress) {
+        if let Some(sink) = &self.sink {
+            if let Err(e) = sink.progress(progress) {
+                log::warn!("Unable to publish the progress of {}: {e:?}", self.game_id);
+            }
+        }
+    }
+}
+
+struct ChannelSink {
+    responses: Sender<GameStatus>,
+    progress: Option<Sender<GameProgress>>,
+}
+
+impl ResponseSink for ChannelSink {
+    fn publish(&self, response: GameStatus) -> Result<(), SimulatorError> {
+        self.responses.send(response).map_err(|e| {
+            SimulatorError::UnidentifiedError(format!("Nobody is listening for {}", e.0.game_id))
+        })
+    }
+
+    fn progress(&self, progress: GameProgress) -> Result<(), SimulatorError> {
+        match &self.progress {
+            Some(s) => s.send(progress).map_err(|e| {
+                SimulatorError::UnidentifiedError(format!(
+                    "Nobody is listening for {}",
+                    e.0.game_id
+                ))
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Takes requests from a channel and publishes their statuses to another, to play games
+/// without a broker, e.g. in tests.
+pub struct ChannelSource {
+    requests: Receiver<GameRequest>,
+    responses: Sender<GameStatus>,
+    progress: Option<Sender<GameProgress>>,
+    grace_period: Duration,
+}
+
+impl ChannelSource {
+    /// The source, along with the ends to send requests into and receive statuses from.
+    /// Running games get `grace_period` to finish once the source is stopped.
+    pub fn new(grace_period: Duration) -> (Sender<GameRequest>, Self, Receiver<GameStatus>) {
+        let (request_s, requests) = crossbeam_channel::unbounded();
+        let (responses, response_r) = crossbeam_channel::unbounded();
+        let source = ChannelSource {
+            requests,
+            responses,
+            progress: None,
+            grace_period,
+        };
+        (request_s, source, response_r)
+    }
+
+    /// Where the progress of running games goes, which is dropped otherwise.
+    pub fn progress(&mut self) -> Receiver<GameProgress> {
+        let (s, r) = crossbeam_channel::unbounded();
+        self.progress = Some(s);
+        r
+    }
+}
+
+impl RequestSource for ChannelSource {
+    fn run(self: Box<Self>, workers: Workers, stop: Receiver<()>) -> Result<(), SimulatorError> {
+        let ChannelSource {
+            mut requests,
+            responses,
+            progress,
+            grace_period,
+        } = *self;
+        let sink: Arc<dyn ResponseSink> = Arc::new(ChannelSink {
+            responses,
+            progress,
+        });
+        loop {
+            select! {
+                recv(requests) -> request => match request {
+                    Ok(request) => {
+                        let queued = create_stage_response(request.game_id(), GameStage::QUEUED);
+                        let _ = sink.publish(queued);
+                        let job = Job {
+                            request,
+                            ticket: Ticket::detached(),
+                            sink: Arc::clone(&sink),
+                        };
+                        let _ = workers.sender(&job.request).send(job);
+                    }
+                    // Nothing more to play, but the games handed out may still be running
+                    Err(_) => requests = never(),
+                },
+                recv(stop) -> _ => break,
+            }
+        }
+
+        if !workers.finish(Instant::now() + grace_period) {
+            log::warn!("Games still running after the grace period were abandoned");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::config::DriverConfig;
+
+/// Tells apart the temporary files of artifacts stored at the same time.
+static STORING: AtomicUsize = AtomicUsize::new(0);
+
+/// Compiled players on local disk, keyed by a hash of everything that went into compiling
+/// them. The least recently used are evicted once the cache outgrows its size.
+pub struct ArtifactCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ArtifactCache {
+    /// The cache configured by `artifact_cache_dir`, `None` if it is disabled.
+    pub fn new(config: &DriverConfig) -> Option<Self> {
+        (!config.artifact_cache_dir.is_empty()).then(|| ArtifactCache {
+            dir: PathBuf::from(&config.artifact_cache_dir),
+            max_bytes: config.artifact_cache_size << 20,
+        })
+    }
+
+    /// Hashes `parts` along with the name and contents of every file under `dir`.
+    pub fn key(dir: &Path, parts: &[&str]) -> io::Result<String> {
+        let mut files = vec![];
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(next) = pending.pop() {
+            for entry in fs::read_dir(next)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+
+        let mut hasher = Sha256::new();
+        let mut feed = |bytes: &[u8]| {
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        };
+        for part in parts {
+            feed(part.as_bytes());
+        }
+        for file in files {
+            let name = file.strip_prefix(dir).unwrap_or(&file);
+            feed(name.to_string_lossy().as_bytes());
+            feed(&fs::read(&file)?);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Copies the artifact stored under `key` to `artifact`, returning whether there was one.
+    pub fn restore(&self, key: &str, artifact: &Path) -> bool {
+        let cached = self.dir.join(key);
+        if fs::copy(&cached, artifact).is_err() {
+            return false;
+        }
+        // Marks it as recently used
+        let _ = File::options()
+            .write(true)
+            .open(&cached)
+            .and_then(|f| f.set_modified(SystemTime::now()));
+        true
+    }
+
+    /// Stores `artifact` under `key`, then evicts the least recently used artifacts that don't
+    /// fit anymore.
+    pub fn store(&self, key: &str, artifact: &Path) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let n = STORING.fetch_add(1, Ordering::Relaxed);
+        let temp = self
+            .dir
+            .join(format!("{key}.{}.{n}.tmp", std::process::id()));
+        fs::copy(artifact, &temp)?;
+        fs::rename(&temp, self.dir.join(key)).inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })?;
+        self.evict()
+    }
+
+    fn evict(&self) -> io::Result<()> {
+        let mut artifacts = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let is_temp = entry.path().extension().is_some_and(|e| e == "tmp");
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !is_temp && metadata.is_file() {
+                let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                artifacts.push((used, metadata.len(), entry.path()));
+            }
+        }
+        artifacts.sort();
+
+        let mut size: u64 = artifacts.iter().map(|(_, len, _)| len).sum();
+        for (_, len, path) in artifacts {
+            if size <= self.max_bytes {
+                break;
+            }
+            // Another worker may have evicted it already
+            let _ = fs::remove_file(path);
+            size -= len;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::ArtifactCache;
+
+    #[test]
+    fn least_recently_used_artifacts_are_evicted() {
+        let root = std::env::temp_dir().join(format!("cc-driver-cache-{}", std::process::id()));
+        let code = root.join("code");
+        fs::create_dir_all(&code).unwrap();
+        fs::write(code.join("run.cpp"), "int main() {}").unwrap();
+
+        let key = ArtifactCache::key(&code, &["CPP", "normal"]).unwrap();
+        assert_eq!(key, ArtifactCache::key(&code, &["CPP", "normal"]).unwrap());
+        assert_ne!(key, ArtifactCache::key(&code, &["CPP", "pvp"]).unwrap());
+        fs::write(code.join("run.cpp"), "int main() { return 1; }").unwrap();
+        assert_ne!(key, ArtifactCache::key(&code, &["CPP", "normal"]).unwrap());
+
+        let cache = ArtifactCache {
+            dir: root.join("cache"),
+            max_bytes: 10,
+        };
+        let artifact = |name: &str| -> PathBuf {
+            let path = root.join(name);
+            fs::write(&path, "12345").unwrap();
+            path
+        };
+        cache.store("a", &artifact("a")).unwrap();
+        cache.store("b", &artifact("b")).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(cache.restore("a", &root.join("restored")));
+        assert_eq!(fs::read(root.join("restored")).unwrap(), b"12345");
+
+        cache.store("c", &artifact("c")).unwrap();
+        assert!(cache.restore("a", &root.join("restored")));
+        assert!(!cache.restore("b", &root.join("restored")));
+        assert!(cache.restore("c", &root.join("restored")));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::HashMap,
+    fs,
+    os::linux::process::CommandExt,
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::error::SimulatorError;
+
+use super::{
+    ContainerBackend, ContainerExit, ContainerSpec, ContainerUsage, GAME_LABEL, INSTANCE_LABEL,
+};
+
+/// A container's id, whether its `docker start` child exited and the thread watching it.
+type Watcher = (String, Arc<AtomicBool>, JoinHandle<ContainerExit>);
+
+/// Runs containers through the `docker` CLI.
+///
+/// Containers are created up front, which names them by their id, and then started with the
+/// CLI attached as the child. They aren't created with `--rm`, so whether they were OOM killed
+/// can still be inspected once they stop. A watcher per container does that and then removes
+/// it, sampling what the container used from its cgroup while it runs.
+#[derive(Default)]
+pub struct DockerCli {
+    /// Keyed by the pid of the `docker start` child, with whether that child exited.
+    watchers: Mutex<HashMap<u32, Watcher>>,
+}
+
+impl DockerCli {
+    /// The `docker create` args of the container.
+    pub fn args(spec: &ContainerSpec) -> Vec<String> {
+        let mut args = vec![
+            "create".to_owned(),
+            format!("--memory={}", spec.limits.memory),
+            format!("--memory-swap={}", spec.limits.memory),
+            format!("--cpus={}", spec.limits.cpus),
+            "--ulimit".to_owned(),
+            // SIGXCPU at the soft limit tells a time limit from other kills
+            format!("cpu={}:{}", spec.limits.cpu_time, spec.limits.cpu_time + 1),
+            "--name".to_owned(),
+            spec.name.to_owned(),
+        ];
+        for (key, value) in spec.labels.iter() {
+            args.push("--label".to_owned());
+            args.push(format!("{key}={value}"));
+        }
+        if spec.stdin.is_some() {
+            args.push("-i".to_owned());
+        }
+        for mount in spec.mounts.iter() {
+            args.push("-v".to_owned());
+            args.push(format!("{}:{}", mount.host_path, mount.container_path));
+        }
+        args.push(spec.image.to_owned());
+        args.extend(spec.args.iter().cloned());
+        args
+    }
+
+    fn docker(args: &[String]) -> Result<String, SimulatorError> {
+        let out = Command::new("docker").args(args).output().map_err(|err| {
+            SimulatorError::UnidentifiedError(format!("Couldnt run docker {}: {err}", args[0]))
+        })?;
+        if !out.status.success() {
+            return Err(SimulatorError::UnidentifiedError(format!(
+                "docker {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&out.stderr)
+            )));
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+    }
+
+    /// Waits for the container `id` to stop, then inspects and removes it.
+    fn watch(id: &str, child_exited: &AtomicBool) -> ContainerExit {
+        // The container only has a pid once `docker start` got to it, right after it is spawned
+        let pid = loop {
+            let exited = child_exited.load(Ordering::Acquire);
+            let inspected = DockerCli::docker(&[
+                "inspect".to_owned(),
+                "--format".to_owned(),
+                "{{.State.Status}} {{.State.Pid}}".to_owned(),
+                id.to_owned(),
+            ]);
+            let state = inspected.map(|out| {
+                let (status, pid) = out.trim().split_once(' ').unwrap_or_default();
+                (status.to_owned(), pid.to_owned())
+            });
+            match state {
+                Ok((status, _)) if status == "created" && !exited => {
+                    thread::sleep(Duration::from_millis(10))
+                }
+                // `docker start` failed, there's nothing to wait for
+                Ok((status, _)) if status == "created" => {
+                    DockerCli::remove(id);
+                    return ContainerExit::default();
+                }
+                Ok((_, pid)) => break Some(pid),
+                Err(e) => {
+                    log::warn!("Unable to inspect the {id} container: {e:?}");
+                    break None;
+                }
+            }
+        };
+
+        // The cgroup goes away as soon as the container stops, so it is read until then
+        let mut usage = None;
+        if let Some(cgroup) = pid.as_deref().and_then(CgroupStats::of) {
+            while let Some(sample) = cgroup.sample() {
+                let peak = usage.map_or(0, |u: ContainerUsage| u.peak_memory_kb);
+                usage = Some(ContainerUsage {
+                    peak_memory_kb: sample.peak_memory_kb.max(peak),
+                    ..sample
+                });
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+        if let Err(e) = DockerCli::docker(&["wait".to_owned(), id.to_owned()]) {
+            log::warn!("Unable to wait for the {id} container: {e:?}");
+        }
+        let oom_killed = DockerCli::docker(&[
+            "inspect".to_owned(),
+            "--format".to_owned(),
+            "{{.State.OOMKilled}}".to_owned(),
+            id.to_owned(),
+        ])
+        .is_ok_and(|out| out.trim() == "true");
+        DockerCli::remove(id);
+        ContainerExit { oom_killed, usage }
+    }
+
+    fn remove(container: &str) {
+        if let Err(e) =
+            DockerCli::docker(&["rm".to_owned(), "--force".to_owned(), container.to_owned()])
+        {
+            log::warn!("Unable to remove the {container} container: {e:?}");
+        }
+    }
+}
+
+/// Where the usage of a running container is read from, on either cgroup version.
+enum CgroupStats {
+    V1 { cpuacct: String, memory: String },
+    V2(String),
+}
+
+impl CgroupStats {
+    /// The cgroup of the container whose init is `pid`, as the driver's mount namespace sees it.
+    fn of(pid: &str) -> Option<Self> {
+        let cgroups = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+        let path = |controller: &str| {
+            cgroups.lines().find_map(|line| {
+                let (controllers, path) = line.split_once(':')?.1.split_once(':')?;
+                controllers
+                    .split(',')
+                    .any(|c| c == controller)
+                    .then(|| format!("/sys/fs/cgroup/{controller}{path}"))
+            })
+        };
+        match (path("cpuacct"), path("memory")) {
+            (Some(cpuacct), Some(memory)) => Some(CgroupStats::V1 { cpuacct, memory }),
+            _ => {
+                let path = cgroups.lines().find_map(|line| line.strip_prefix("0::"))?;
+                Some(CgroupStats::V2(format!("/sys/fs/cgroup{path}")))
+            }
+        }
+    }
+
+    /// What the container used so far, `None` once it stopped.
+    fn sample(&self) -> Option<ContainerUsage> {
+        let read = |path: String| fs::read_to_string(path).ok();
+        let number = |text: &str| text.trim().parse::<u64>().ok();
+        let (cpu_time_ms, memory) = match self {
+            CgroupStats::V1 { cpuacct, memory } => (
+                number(&read(format!("{cpuacct}/cpuacct.usage"))?)? / 1_000_000,
+                read(format!("{memory}/memory.max_usage_in_bytes"))?,
+            ),
+            CgroupStats::V2(path) => {
+                let stat = read(format!("{path}/cpu.stat"))?;
+                let usec = stat
+                    .lines()
+                    .find_map(|line| line.strip_prefix("usage_usec "))
+                    .and_then(number)?;
+                // memory.peak is only there since Linux 5.19, sampling makes do before that
+                let memory = read(format!("{path}/memory.peak"))
+                    .or_else(|| read(format!("{path}/memory.current")))?;
+                (usec / 1000, memory)
+            }
+        };
+        Some(ContainerUsage {
+            cpu_time_ms,
+            peak_memory_kb: number(&memory)? >> 10,
+        })
+    }
+}
+
+impl ContainerBackend for DockerCli {
+    fn spawn(&self, spec: ContainerSpec) -> Result<Child, SimulatorError> {
+        let name = spec.name.to_owned();
+        // Left behind by an earlier attempt at the game, and in the way of its name
+        let _ = DockerCli::docker(&["rm".to_owned(), "--force".to_owned(), name.to_owned()]);
+        let id = DockerCli::docker(&DockerCli::args(&spec))?
+            .trim()
+            .to_owned();
+
+        let mut cmd = Command::new("docker");
+        cmd.args(["start", "--attach"]);
+        if spec.stdin.is_some() {
+            cmd.arg("--interactive");
+        }
+        cmd.arg(&id)
+            .create_pidfd(true)
+            .stdin(spec.stdin.map_or_else(Stdio::null, Stdio::from))
+            .stdout(spec.stdout.map_or_else(Stdio::null, Stdio::from))
+            .stderr(Stdio::piped());
+        let child = cmd.spawn().map_err(|err| {
+            DockerCli::remove(&id);
+            SimulatorError::UnidentifiedError(format!("Couldnt spawn the {name} container: {err}"))
+        })?;
+
+        let exited = Arc::new(AtomicBool::new(false));
+        let watcher = {
+            let (id, exited) = (id.to_owned(), Arc::clone(&exited));
+            thread::spawn(move || DockerCli::watch(&id, &exited))
+        };
+        self.watchers
+            .lock()
+            .unwrap()
+            .insert(child.id(), (id, exited, watcher));
+        Ok(child)
+    }
+
+    /// Killing the CLI leaves the container running under the daemon.
+    fn kill(&self, child: &mut Child) {
+        let id = self
+            .watchers
+            .lock()
+            .unwrap()
+            .get(&child.id())
+            .map(|(id, _, _)| id.to_owned());
+        if let Some(id) = id {
+            let _ = DockerCli::docker(&["kill".to_owned(), id]);
+        }
+        let _ = child.kill();
+    }
+
+    fn finish(&self, pid: u32) -> ContainerExit {
+        let watcher = self.watchers.lock().unwrap().remove(&pid);
+        match watcher {
+            Some((_, exited, watcher)) => {
+                exited.store(true, Ordering::Release);
+                watcher.join().unwrap_or_default()
+            }
+            None => ContainerExit::default(),
+        }
+    }
+
+    fn image_digest(&self, image: &str) -> String {
+        let args = [
+            "image".to_owned(),
+            "inspect".to_owned(),
+            "--format".to_owned(),
+            "{{.Id}}".to_owned(),
+            image.to_owned(),
+        ];
+        match DockerCli::docker(&args) {
+            Ok(id) => id.trim().to_owned(),
+            Err(e) => {
+                log::warn!("Unable to inspect {image}, keying its artifacts by name: {e:?}");
+                image.to_owned()
+            }
+        }
+    }
+
+    /// The child is the docker CLI, the container runs under the daemon and is measured by its
+    /// watcher instead.
+    fn measures_usage(&self) -> bool {
+        false
+    }
+
+    fn reap(
+        &self,
+        instance_id: &str,
+        is_live: &dyn Fn(&str) -> bool,
+    ) -> Result<usize, SimulatorError> {
+        let listing = DockerCli::docker(&[
+            "ps".to_owned(),
+            "--all".to_owned(),
+            "--filter".to_owned(),
+            format!("label={INSTANCE_LABEL}={instance_id}"),
+            "--format".to_owned(),
+            format!("{{{{.ID}}}} {{{{.Label \"{GAME_LABEL}\"}}}}"),
+        ])?;
+        let mut args = vec!["rm".to_owned(), "--force".to_owned()];
+        args.extend(listing.lines().filter_map(|line| {
+            let (id, game_id) = line.split_once(' ').unwrap_or((line, ""));
+            (!id.is_empty() && !is_live(game_id)).then(|| id.to_owned())
+        }));
+        let stale = args.len() - 2;
+        if stale > 0 {
+            DockerCli::docker(&args)?;
+        }
+        Ok(stale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{CgroupStats, DockerCli};
+    use crate::container::{ContainerSpec, ContainerUsage, ResourceLimits};
+
+    #[test]
+    fn samples_cgroup_v2_usage_until_it_is_gone() {
+        let dir = std::env::temp_dir().join(format!("cc-driver-cgroup-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("cpu.stat"),
+            "usage_usec 2500000\nuser_usec 2000000\n",
+        )
+        .unwrap();
+        fs::write(dir.join("memory.current"), "1048576\n").unwrap();
+        let cgroup = CgroupStats::V2(dir.display().to_string());
+        assert_eq!(
+            cgroup.sample(),
+            Some(ContainerUsage {
+                cpu_time_ms: 2500,
+                peak_memory_kb: 1024,
+            })
+        );
+
+        fs::write(dir.join("memory.peak"), "2097152\n").unwrap();
+        assert_eq!(cgroup.sample().unwrap().peak_memory_kb, 2048);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(cgroup.sample(), None);
+    }
+
+    #[test]
+    fn builds_docker_create_args() {
+        let spec = ContainerSpec::new(
+            "1_player_cpp_runner",
+            "cpp-runner",
+            ResourceLimits {
+                memory: "100m".to_owned(),
+                cpus: 1.5,
+                cpu_time: 10,
+            },
+        )
+        .label("game-id", "1")
+        .mount("/tmp/1/player/run", "/player_code")
+        .arg("normal");
+
+        assert_eq!(
+            DockerCli::args(&spec),
+            vec![
+                "create",
+                "--memory=100m",
+                "--memory-swap=100m",
+                "--cpus=1.5",
+                "--ulimit",
+                "cpu=10:11",
+                "--name",
+                "1_player_cpp_runner",
+                "--label",
+                "game-id=1",
+                "-v",
+                "/tmp/1/player/run:/player_code",
+                "cpp-runner",
+                "normal",
+            ]
+        );
+    }
+}
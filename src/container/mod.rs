@@ -0,0 +1,226 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    process::{Child, Output},
+    sync::Arc,
+};
+
+use crate::{
+    config::{ContainerBackendKind, DriverConfig},
+    error::SimulatorError,
+};
+
+pub mod docker;
+pub mod process;
+pub mod sandbox;
+
+/// Labels put on every container, so leftovers from a crashed driver can be found again.
+pub const INSTANCE_LABEL: &str = "cc-driver.instance";
+pub const GAME_LABEL: &str = "cc-driver.game";
+pub const ROLE_LABEL: &str = "cc-driver.role";
+
+/// A host directory made visible inside the container.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mount {
+    pub host_path: String,
+    pub container_path: String,
+}
+
+impl Mount {
+    pub fn new(host_path: impl Into<String>, container_path: impl Into<String>) -> Self {
+        Mount {
+            host_path: host_path.into(),
+            container_path: container_path.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceLimits {
+    /// Memory limit in docker notation (`300m`), also used as the swap limit.
+    pub memory: String,
+    pub cpus: f32,
+    /// CPU time limit in seconds.
+    pub cpu_time: u64,
+}
+
+impl ResourceLimits {
+    /// The memory limit in bytes, `None` if it is not in docker notation.
+    pub fn memory_bytes(&self) -> Option<u64> {
+        let memory = self.memory.to_lowercase();
+        let (digits, unit) = match memory.chars().last()? {
+            'b' => (&memory[..memory.len() - 1], 1),
+            'k' => (&memory[..memory.len() - 1], 1 << 10),
+            'm' => (&memory[..memory.len() - 1], 1 << 20),
+            'g' => (&memory[..memory.len() - 1], 1 << 30),
+            _ => (memory.as_str(), 1),
+        };
+        digits.parse::<u64>().ok().map(|n| n * unit)
+    }
+
+    pub fn compilation(config: &DriverConfig, cpus: f32) -> Self {
+        ResourceLimits {
+            memory: config.compilation_memory_limit.to_owned(),
+            cpus,
+            cpu_time: config.compilation_time_limit,
+        }
+    }
+
+    pub fn runtime(config: &DriverConfig) -> Self {
+        ResourceLimits {
+            memory: config.runtime_memory_limit.to_owned(),
+            cpus: config.runtime_cpus,
+            cpu_time: config.runtime_time_limit,
+        }
+    }
+}
+
+/// Everything needed to start one sandboxed process, independent of how it is isolated.
+///
+/// `stdin`/`stdout` default to `/dev/null` when not set; stderr is always piped back to
+/// the driver so it can be collected as the process log.
+#[derive(Debug)]
+pub struct ContainerSpec {
+    pub name: String,
+    pub image: String,
+    pub args: Vec<String>,
+    pub mounts: Vec<Mount>,
+    pub limits: ResourceLimits,
+    pub labels: BTreeMap<String, String>,
+    pub stdin: Option<File>,
+    pub stdout: Option<File>,
+}
+
+impl ContainerSpec {
+    pub fn new(name: impl Into<String>, image: impl Into<String>, limits: ResourceLimits) -> Self {
+        ContainerSpec {
+            name: name.into(),
+            image: image.into(),
+            args: vec![],
+            mounts: vec![],
+            limits,
+            labels: BTreeMap::new(),
+            stdin: None,
+            stdout: None,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn mount(
+        mut self,
+        host_path: impl Into<String>,
+        container_path: impl Into<String>,
+    ) -> Self {
+        self.mounts.push(Mount::new(host_path, container_path));
+        self
+    }
+
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Labels the container with the driver instance, the game and what it does in the game.
+    pub fn owned_by(self, config: &DriverConfig, game_id: &str, role: &str) -> Self {
+        self.label(INSTANCE_LABEL, &config.instance_id)
+            .label(GAME_LABEL, game_id)
+            .label(ROLE_LABEL, role)
+    }
+
+    pub fn stdin(mut self, stdin: File) -> Self {
+        self.stdin = Some(stdin);
+        self
+    }
+
+    pub fn stdout(mut self, stdout: File) -> Self {
+        self.stdout = Some(stdout);
+        self
+    }
+}
+
+/// What a container used, for backends whose child isn't the contained process.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContainerUsage {
+    pub cpu_time_ms: u64,
+    pub peak_memory_kb: u64,
+}
+
+/// How a container ended, besides the exit status of its child.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ContainerExit {
+    /// Killed by the kernel for going over its memory limit.
+    pub oom_killed: bool,
+    /// Measured by the backend itself, when `measures_usage` is false.
+    pub usage: Option<ContainerUsage>,
+}
+
+pub trait ContainerBackend: Send + Sync {
+    /// Starts the container and returns a pidfd-backed handle with stderr piped.
+    fn spawn(&self, spec: ContainerSpec) -> Result<Child, SimulatorError>;
+
+    /// Runs the container to completion, collecting its stderr.
+    fn run(&self, spec: ContainerSpec) -> Result<Output, SimulatorError> {
+        let name = spec.name.to_owned();
+        let child = self.spawn(spec)?;
+        let pid = child.id();
+        let output = child.wait_with_output().map_err(|err| {
+            SimulatorError::UnidentifiedError(format!("Unable to wait for {name} to finish, {err}"))
+        });
+        self.finish(pid);
+        output
+    }
+
+    /// Kills the container started as `child`, which may not be enough on its own when the
+    /// child only talks to where the container runs.
+    fn kill(&self, child: &mut Child) {
+        let _ = child.kill();
+    }
+
+    /// Called once the child `pid` has been reaped, to tell how its container ended and clean
+    /// it up.
+    fn finish(&self, _pid: u32) -> ContainerExit {
+        ContainerExit::default()
+    }
+
+    /// Identifies what `image` currently refers to, so artifacts built with it can be told
+    /// apart from those built with an earlier version. Falls back to the image's name.
+    fn image_digest(&self, image: &str) -> String {
+        image.to_owned()
+    }
+
+    /// What `image` is started with ahead of a spec's args, when that is configured for the
+    /// backend rather than part of the image.
+    fn entrypoint(&self, _image: &str) -> Vec<String> {
+        vec![]
+    }
+
+    /// Whether what a spawned child used, as reported by `wait4`, is what the contained process
+    /// used. Otherwise it comes with the `ContainerExit`, if the backend could tell.
+    fn measures_usage(&self) -> bool {
+        true
+    }
+
+    /// Kills the containers labelled with `instance_id` whose game is not running anymore and
+    /// returns how many were removed. Backends that can't outlive the driver have nothing to do.
+    fn reap(
+        &self,
+        _instance_id: &str,
+        _is_live: &dyn Fn(&str) -> bool,
+    ) -> Result<usize, SimulatorError> {
+        Ok(0)
+    }
+}
+
+pub fn from_config(config: &DriverConfig) -> Arc<dyn ContainerBackend> {
+    match config.container_backend {
+        ContainerBackendKind::Docker => Arc::new(docker::DockerCli::default()),
+        ContainerBackendKind::Sandbox => Arc::new(sandbox::Sandbox::new(config.sandbox.clone())),
+        ContainerBackendKind::Process => {
+            Arc::new(process::LocalProcess::new(config.process_images.clone()))
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    collections::BTreeMap,
+    os::{linux::process::CommandExt as _, unix::process::CommandExt},
+    process::{Child, Command, Stdio},
+};
+
+use nix::sys::resource::{setrlimit, Resource};
+
+use crate::{config::ProcessImage, error::SimulatorError};
+
+use super::{ContainerBackend, ContainerSpec, Mount};
+
+/// Runs every image as a plain local process, without any isolation besides the CPU time
+/// limit. Meant for hermetic tests and local development, never for untrusted code.
+pub struct LocalProcess {
+    images: BTreeMap<String, ProcessImage>,
+}
+
+impl LocalProcess {
+    pub fn new(images: BTreeMap<String, ProcessImage>) -> Self {
+        LocalProcess { images }
+    }
+
+    /// Maps a path inside the container to the host path behind its longest matching mount.
+    pub fn host_path(mounts: &[Mount], path: &str) -> String {
+        mounts
+            .iter()
+            .filter_map(|mount| {
+                let prefix = mount.container_path.trim_end_matches('/');
+                let rest = path.strip_prefix(prefix)?;
+                (rest.is_empty() || rest.starts_with('/')).then_some((prefix.len(), mount, rest))
+            })
+            .max_by_key(|(len, _, _)| *len)
+            .map(|(_, mount, rest)| format!("{}{rest}", mount.host_path.trim_end_matches('/')))
+            .unwrap_or_else(|| path.to_owned())
+    }
+}
+
+impl ContainerBackend for LocalProcess {
+    fn spawn(&self, spec: ContainerSpec) -> Result<Child, SimulatorError> {
+        let image = self.images.get(&spec.image).ok_or_else(|| {
+            SimulatorError::UnidentifiedError(format!(
+                "No local command configured for {}",
+                spec.image
+            ))
+        })?;
+
+        let name = spec.name.to_owned();
+        let mut cmd = Command::new(&image.command[0]);
+        cmd.args(&image.command[1..])
+            .args(
+                spec.args
+                    .iter()
+                    .map(|arg| LocalProcess::host_path(&spec.mounts, arg)),
+            )
+            .envs(&image.env)
+            .create_pidfd(true)
+            .stdin(spec.stdin.map_or_else(Stdio::null, Stdio::from))
+            .stdout(spec.stdout.map_or_else(Stdio::null, Stdio::from))
+            .stderr(Stdio::piped());
+        if let Some(workdir) = &image.workdir {
+            cmd.current_dir(LocalProcess::host_path(&spec.mounts, workdir));
+        }
+        let cpu_time = spec.limits.cpu_time;
+        // SAFETY: setrlimit is async-signal-safe
+        unsafe {
+            cmd.pre_exec(move || {
+                // SIGXCPU at the soft limit tells a time limit from other kills
+                setrlimit(Resource::RLIMIT_CPU, cpu_time, cpu_time + 1).map_err(|e| e.into())
+            });
+        }
+
+        cmd.spawn().map_err(|err| {
+            SimulatorError::UnidentifiedError(format!("Couldnt spawn the {name} process: {err}"))
+        })
+    }
+
+    fn entrypoint(&self, image: &str) -> Vec<String> {
+        self.images
+            .get(image)
+            .map(|image| image.command.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LocalProcess;
+    use crate::container::Mount;
+
+    #[test]
+    fn maps_container_paths_to_host_paths() {
+        let mounts = vec![
+            Mount::new("/tmp/1/player/", "/player_code/"),
+            Mount::new("/tmp/1/player/run.jar", "/player_code/run.jar"),
+        ];
+        assert_eq!(
+            LocalProcess::host_path(&mounts, "/player_code"),
+            "/tmp/1/player"
+        );
+        assert_eq!(
+            LocalProcess::host_path(&mounts, "/player_code/main.py"),
+            "/tmp/1/player/main.py"
+        );
+        assert_eq!(
+            LocalProcess::host_path(&mounts, "/player_code/run.jar"),
+            "/tmp/1/player/run.jar"
+        );
+        assert_eq!(
+            LocalProcess::host_path(&mounts, "/player_codes"),
+            "/player_codes"
+        );
+        assert_eq!(LocalProcess::host_path(&mounts, "normal"), "normal");
+    }
+}
@@ -0,0 +1,975 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::CString,
+    fs, io,
+    os::{
+        fd::AsRawFd,
+        linux::process::{ChildExt, CommandExt as _},
+        unix::{ffi::OsStrExt, process::CommandExt},
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::Mutex,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use nix::{
+    libc,
+    poll::{poll, PollFd, PollFlags},
+    unistd::{close, dup, getgid, getuid},
+};
+
+use crate::{
+    config::{SandboxConfig, SandboxImage},
+    error::SimulatorError,
+};
+
+use super::{
+    ContainerBackend, ContainerExit, ContainerSpec, ResourceLimits, GAME_LABEL, INSTANCE_LABEL,
+};
+
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// Runs images straight on the host, isolated with user/pid/mount/net namespaces, a cgroup v2
+/// leaf for the resource limits, no capabilities and a seccomp allowlist. Each image must be unpacked to a
+/// root filesystem and listed under `[sandbox_images]` in the driver config.
+pub struct Sandbox {
+    config: SandboxConfig,
+    /// Remove the cgroup leaf of each child once it exits, keyed by the child's pid.
+    cleanups: Mutex<HashMap<u32, JoinHandle<ContainerExit>>>,
+}
+
+impl Sandbox {
+    pub fn new(config: SandboxConfig) -> Self {
+        Sandbox {
+            config,
+            cleanups: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn image(&self, image: &str) -> Result<&SandboxImage, SimulatorError> {
+        self.config.images.get(image).ok_or_else(|| {
+            SimulatorError::UnidentifiedError(format!("No sandbox rootfs configured for {image}"))
+        })
+    }
+}
+
+impl ContainerBackend for Sandbox {
+    fn spawn(&self, spec: ContainerSpec) -> Result<Child, SimulatorError> {
+        let image = self.image(&spec.image)?;
+        let name = spec.name.to_owned();
+        let map_err = |err: io::Error| {
+            SimulatorError::UnidentifiedError(format!("Couldnt start the {name} sandbox: {err}"))
+        };
+
+        // A driver running as root hands the sandbox an unprivileged uid instead of its own,
+        // which needs to own what is mounted in to write there
+        let ids = getuid()
+            .is_root()
+            .then_some((self.config.uid, self.config.gid));
+        if let Some((uid, gid)) = ids {
+            for mount in spec.mounts.iter() {
+                chown_all(Path::new(&mount.host_path), uid, gid).map_err(map_err)?;
+            }
+        }
+
+        // Labelled containers are grouped as <root>/<instance>/<game>/<name>, so a whole game
+        // can be killed at once by the reaper
+        let group = match (spec.labels.get(INSTANCE_LABEL), spec.labels.get(GAME_LABEL)) {
+            (Some(instance), Some(game)) => {
+                vec![instance.replace('/', "_"), game.replace('/', "_")]
+            }
+            _ => vec![],
+        };
+        let cgroup = Cgroup::create(
+            &self.config.cgroup_root,
+            &group,
+            &spec.name,
+            &spec.limits,
+            self.config.pids_limit,
+        )
+        .map_err(map_err)?;
+        let plan = ExecPlan::new(image, &spec, &cgroup, ids, self.config.seccomp);
+        let plan = match plan {
+            Ok(plan) => plan,
+            Err(err) => {
+                cgroup.destroy();
+                return Err(map_err(err));
+            }
+        };
+
+        let mut cmd = Command::new(&image.entrypoint[0]);
+        cmd.args(&image.entrypoint[1..])
+            .args(&spec.args)
+            .env_clear()
+            .env("PATH", DEFAULT_PATH)
+            .envs(&image.env)
+            .create_pidfd(true)
+            .stdin(spec.stdin.map_or_else(Stdio::null, Stdio::from))
+            .stdout(spec.stdout.map_or_else(Stdio::null, Stdio::from))
+            .stderr(Stdio::piped());
+        // SAFETY: `ExecPlan::enter` only makes raw syscalls on memory prepared before the fork.
+        unsafe {
+            cmd.pre_exec(move || plan.enter());
+        }
+
+        let child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                cgroup.destroy();
+                return Err(map_err(err));
+            }
+        };
+
+        // The leaf is removed once the process exits; a dup of the pidfd lets us wait for
+        // that without reaping the child from under the epoll loop.
+        let pidfd = child
+            .pidfd()
+            .map_err(map_err)
+            .and_then(|fd| dup(fd.as_raw_fd()).map_err(|e| map_err(e.into())))?;
+        let cleanup = thread::spawn(move || {
+            let mut fds = [PollFd::new(pidfd, PollFlags::POLLIN)];
+            while let Err(nix::errno::Errno::EINTR) = poll(&mut fds, -1) {}
+            let _ = close(pidfd);
+            let exit = ContainerExit {
+                oom_killed: cgroup.oom_kills() > 0,
+                ..ContainerExit::default()
+            };
+            cgroup.destroy();
+            exit
+        });
+        self.cleanups.lock().unwrap().insert(child.id(), cleanup);
+
+        Ok(child)
+    }
+
+    fn finish(&self, pid: u32) -> ContainerExit {
+        let cleanup = self.cleanups.lock().unwrap().remove(&pid);
+        cleanup
+            .and_then(|cleanup| cleanup.join().ok())
+            .unwrap_or_default()
+    }
+
+    fn reap(
+        &self,
+        instance_id: &str,
+        is_live: &dyn Fn(&str) -> bool,
+    ) -> Result<usize, SimulatorError> {
+        let map_err = |err: io::Error| {
+            SimulatorError::UnidentifiedError(format!("Couldnt list the sandbox cgroups: {err}"))
+        };
+        let instance = format!(
+            "{}/{}",
+            self.config.cgroup_root,
+            instance_id.replace('/', "_")
+        );
+        let games = match fs::read_dir(&instance) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            res => res.map_err(map_err)?,
+        };
+
+        let mut reaped = 0;
+        for game in games.flatten() {
+            if !game.path().is_dir() || is_live(&game.file_name().to_string_lossy()) {
+                continue;
+            }
+            let group = Cgroup {
+                path: game.path().to_string_lossy().into_owned(),
+            };
+            // Kills every leaf of the game at once, the leaves must go before their parent
+            let _ = group.write("cgroup.kill", "1");
+            for leaf in fs::read_dir(&group.path).map_err(map_err)?.flatten() {
+                if leaf.path().is_dir() {
+                    Cgroup {
+                        path: leaf.path().to_string_lossy().into_owned(),
+                    }
+                    .destroy();
+                    reaped += 1;
+                }
+            }
+            group.destroy();
+        }
+        Ok(reaped)
+    }
+
+    fn entrypoint(&self, image: &str) -> Vec<String> {
+        self.image(image)
+            .map(|image| image.entrypoint.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Changes the owner of `path` and of everything under it.
+fn chown_all(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+    std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+    if fs::symlink_metadata(path)?.is_dir() {
+        for entry in fs::read_dir(path)? {
+            chown_all(&entry?.path(), uid, gid)?;
+        }
+    }
+    Ok(())
+}
+
+struct Cgroup {
+    path: String,
+}
+
+impl Cgroup {
+    fn create(
+        root: &str,
+        group: &[String],
+        name: &str,
+        limits: &ResourceLimits,
+        pids_limit: u64,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(root)?;
+        let mut parent = root.to_owned();
+        Cgroup::delegate(&parent);
+        for dir in group {
+            parent = format!("{parent}/{dir}");
+            match fs::create_dir(&parent) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                res => res?,
+            }
+            Cgroup::delegate(&parent);
+        }
+
+        let cgroup = Cgroup {
+            path: format!("{parent}/{name}"),
+        };
+        match fs::create_dir(&cgroup.path) {
+            // Left behind by a crashed driver, make sure nothing is still running in it
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let _ = fs::write(format!("{}/cgroup.kill", cgroup.path), "1");
+            }
+            res => res?,
+        }
+
+        let memory = limits.memory_bytes().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid memory limit {}", limits.memory),
+            )
+        })?;
+        let period = 100_000;
+        cgroup.write("memory.max", &memory.to_string())?;
+        cgroup.write("memory.swap.max", "0")?;
+        cgroup.write(
+            "cpu.max",
+            &format!("{} {period}", (limits.cpus * period as f32) as u64),
+        )?;
+        cgroup.write("pids.max", &pids_limit.to_string())?;
+        Ok(cgroup)
+    }
+
+    fn delegate(dir: &str) {
+        // Fails harmlessly when the controllers are already delegated to the children
+        let _ = fs::write(
+            format!("{dir}/cgroup.subtree_control"),
+            "+memory +cpu +pids",
+        );
+    }
+
+    /// How many processes of the leaf the OOM killer killed.
+    fn oom_kills(&self) -> u64 {
+        let events = fs::read_to_string(format!("{}/memory.events", self.path)).unwrap_or_default();
+        events
+            .lines()
+            .find_map(|line| line.strip_prefix("oom_kill "))
+            .and_then(|n| n.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    fn write(&self, file: &str, value: &str) -> io::Result<()> {
+        fs::write(format!("{}/{file}", self.path), value)
+    }
+
+    fn destroy(&self) {
+        let _ = self.write("cgroup.kill", "1");
+        for _ in 0..50 {
+            if fs::remove_dir(&self.path).is_ok() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        log::warn!("Unable to remove sandbox cgroup {}", self.path);
+    }
+}
+
+/// Everything the child needs between `fork` and `exec`, allocated up front since only
+/// async-signal-safe calls may be made there.
+///
+/// The container's root is an overlay of the image's rootfs, which every container shares and
+/// never writes to, under two layers on a tmpfs of its own: one holding the mount targets and
+/// one taking its writes. The tmpfs is mounted over a scratch directory only inside the
+/// container's mount namespace, so it goes away with the container.
+struct ExecPlan {
+    cgroup_procs: CString,
+    /// The uid and gid to switch to before entering the namespaces.
+    ids: Option<(u32, u32)>,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    scratch: CString,
+    /// Directories to create on the tmpfs, parents first.
+    dirs: Vec<CString>,
+    /// The tmpfs' `/tmp`, left writable by everyone like a usual `/tmp`.
+    tmp: CString,
+    /// Files to create on the tmpfs as targets of file mounts.
+    files: Vec<CString>,
+    overlay: CString,
+    root: CString,
+    mounts: Vec<(CString, CString)>,
+    proc_dir: CString,
+    workdir: CString,
+    cpu_time: u64,
+    seccomp: Option<Vec<libc::sock_filter>>,
+}
+
+fn cstring(s: impl AsRef<[u8]>) -> io::Result<CString> {
+    CString::new(s.as_ref()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+fn path_cstring(path: &Path) -> io::Result<CString> {
+    cstring(path.as_os_str().as_bytes())
+}
+
+impl ExecPlan {
+    fn new(
+        image: &SandboxImage,
+        spec: &ContainerSpec,
+        cgroup: &Cgroup,
+        ids: Option<(u32, u32)>,
+        seccomp: bool,
+    ) -> io::Result<Self> {
+        let rootfs = Path::new(&image.rootfs);
+        let scratch = env::temp_dir().join("cc-driver-sandbox");
+        fs::create_dir_all(&scratch)?;
+        let (layer, upper, work, root) = (
+            scratch.join("layer"),
+            scratch.join("upper"),
+            scratch.join("work"),
+            scratch.join("root"),
+        );
+
+        let mut dirs = vec![
+            layer.clone(),
+            upper.clone(),
+            work.clone(),
+            root.clone(),
+            layer.join("tmp"),
+            layer.join("proc"),
+        ];
+        let mut add_dir = |dir: PathBuf| {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        };
+        let mut files = vec![];
+        let mut mounts = vec![];
+        for mount in spec.mounts.iter() {
+            let source = Path::new(&mount.host_path);
+            let target = Path::new(mount.container_path.trim_start_matches('/'));
+            // Bind mounts need an existing target of the same kind
+            let mut parents: Vec<_> = target
+                .ancestors()
+                .skip(1)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .collect();
+            parents.reverse();
+            for dir in parents {
+                add_dir(layer.join(dir));
+            }
+            if source.is_dir() {
+                add_dir(layer.join(target));
+            } else {
+                files.push(path_cstring(&layer.join(target))?);
+            }
+            mounts.push((path_cstring(source)?, path_cstring(&root.join(target))?));
+        }
+
+        let overlay = format!(
+            "lowerdir={}:{},upperdir={},workdir={}",
+            layer.display(),
+            rootfs.display(),
+            upper.display(),
+            work.display()
+        );
+        let (uid, gid) = ids.unwrap_or_else(|| (getuid().as_raw(), getgid().as_raw()));
+
+        Ok(ExecPlan {
+            cgroup_procs: cstring(format!("{}/cgroup.procs", cgroup.path))?,
+            ids,
+            uid_map: format!("0 {uid} 1").into_bytes(),
+            gid_map: format!("0 {gid} 1").into_bytes(),
+            scratch: path_cstring(&scratch)?,
+            dirs: dirs
+                .iter()
+                .map(|dir| path_cstring(dir))
+                .collect::<io::Result<_>>()?,
+            tmp: path_cstring(&layer.join("tmp"))?,
+            files,
+            overlay: cstring(overlay)?,
+            root: path_cstring(&root)?,
+            mounts,
+            proc_dir: path_cstring(&root.join("proc"))?,
+            workdir: cstring(&image.workdir)?,
+            cpu_time: spec.limits.cpu_time,
+            seccomp: match seccomp {
+                true => Some(seccomp::filter()?),
+                false => None,
+            },
+        })
+    }
+
+    /// Runs in the forked child right before `exec`.
+    ///
+    /// The child forks twice more: the new pid namespace only applies to children, and its pid 1
+    /// ignores the signals it has no handler for, so the player runs as pid 2 under a minimal
+    /// init. The child and the init only relay the player's exit status.
+    fn enter(&self) -> io::Result<()> {
+        unsafe {
+            write_file(self.cgroup_procs.as_ptr(), b"0")?;
+            // Only the ids a process has can be mapped into its user namespace
+            if let Some((uid, gid)) = self.ids {
+                check(libc::setgroups(0, std::ptr::null()))?;
+                check(libc::setresgid(gid, gid, gid))?;
+                check(libc::setresuid(uid, uid, uid))?;
+                // Changing ids hands /proc/self, where the maps are written, to root
+                check(libc::prctl(libc::PR_SET_DUMPABLE, 1, 0, 0, 0))?;
+            }
+            check(libc::unshare(
+                libc::CLONE_NEWUSER
+                    | libc::CLONE_NEWNS
+                    | libc::CLONE_NEWPID
+                    | libc::CLONE_NEWNET
+                    | libc::CLONE_NEWIPC
+                    | libc::CLONE_NEWUTS,
+            ))?;
+            write_file(b"/proc/self/setgroups\0".as_ptr().cast(), b"deny")?;
+            write_file(b"/proc/self/uid_map\0".as_ptr().cast(), &self.uid_map)?;
+            write_file(b"/proc/self/gid_map\0".as_ptr().cast(), &self.gid_map)?;
+
+            match libc::fork() {
+                -1 => return Err(io::Error::last_os_error()),
+                0 => {}
+                pid => supervise(pid),
+            }
+            check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL, 0, 0, 0))?;
+
+            check(libc::mount(
+                std::ptr::null(),
+                b"/\0".as_ptr().cast(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            ))?;
+            check(libc::mount(
+                b"tmpfs\0".as_ptr().cast(),
+                self.scratch.as_ptr(),
+                b"tmpfs\0".as_ptr().cast(),
+                libc::MS_NOSUID | libc::MS_NODEV,
+                std::ptr::null(),
+            ))?;
+            for dir in self.dirs.iter() {
+                check(libc::mkdir(dir.as_ptr(), 0o755))?;
+            }
+            check(libc::chmod(self.tmp.as_ptr(), 0o1777))?;
+            for file in self.files.iter() {
+                let fd = libc::open(
+                    file.as_ptr(),
+                    libc::O_CREAT | libc::O_WRONLY | libc::O_CLOEXEC,
+                    0o644,
+                );
+                check(fd)?;
+                libc::close(fd);
+            }
+            check(libc::mount(
+                b"overlay\0".as_ptr().cast(),
+                self.root.as_ptr(),
+                b"overlay\0".as_ptr().cast(),
+                libc::MS_NOSUID | libc::MS_NODEV,
+                self.overlay.as_ptr().cast(),
+            ))?;
+            for (source, target) in self.mounts.iter() {
+                check(libc::mount(
+                    source.as_ptr(),
+                    target.as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_BIND | libc::MS_REC,
+                    std::ptr::null(),
+                ))?;
+            }
+            check(libc::mount(
+                b"proc\0".as_ptr().cast(),
+                self.proc_dir.as_ptr(),
+                b"proc\0".as_ptr().cast(),
+                libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                std::ptr::null(),
+            ))?;
+            // Stacks the old root under the new one and detaches it, unlike chroot which
+            // leaves the host's filesystem reachable
+            check(libc::chdir(self.root.as_ptr()))?;
+            check(libc::syscall(libc::SYS_pivot_root, b".\0".as_ptr(), b".\0".as_ptr()) as i32)?;
+            check(libc::umount2(b".\0".as_ptr().cast(), libc::MNT_DETACH))?;
+            check(libc::chdir(b"/\0".as_ptr().cast()))?;
+            check(libc::chdir(self.workdir.as_ptr()))?;
+
+            match libc::fork() {
+                -1 => return Err(io::Error::last_os_error()),
+                0 => {}
+                pid => init(pid),
+            }
+
+            // SIGXCPU at the soft limit tells a time limit from other kills
+            let cpu = libc::rlimit {
+                rlim_cur: self.cpu_time,
+                rlim_max: self.cpu_time + 1,
+            };
+            check(libc::setrlimit(libc::RLIMIT_CPU, &cpu))?;
+            drop_capabilities()?;
+
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+            if let Some(filter) = &self.seccomp {
+                let prog = libc::sock_fprog {
+                    len: filter.len() as u16,
+                    filter: filter.as_ptr() as *mut libc::sock_filter,
+                };
+                check(libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &prog as *const libc::sock_fprog,
+                ))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    match ret {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+unsafe fn write_file(path: *const libc::c_char, data: &[u8]) -> io::Result<()> {
+    let fd = libc::open(path, libc::O_WRONLY | libc::O_CLOEXEC);
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let written = libc::write(fd, data.as_ptr().cast(), data.len());
+    libc::close(fd);
+    match written {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// Drops every capability the user namespace gave, including from the bounding set so
+/// executing a setuid binary or running as root in the namespace can't bring them back.
+unsafe fn drop_capabilities() -> io::Result<()> {
+    let mut cap = 0;
+    while libc::prctl(libc::PR_CAPBSET_READ, cap, 0, 0, 0) >= 0 {
+        check(libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0))?;
+        cap += 1;
+    }
+    check(libc::prctl(
+        libc::PR_CAP_AMBIENT,
+        libc::PR_CAP_AMBIENT_CLEAR_ALL,
+        0,
+        0,
+        0,
+    ))?;
+
+    #[repr(C)]
+    struct CapHeader {
+        version: u32,
+        pid: libc::c_int,
+    }
+    #[repr(C)]
+    struct CapData {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+    const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+    let header = CapHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let data = [
+        CapData {
+            effective: 0,
+            permitted: 0,
+            inheritable: 0,
+        },
+        CapData {
+            effective: 0,
+            permitted: 0,
+            inheritable: 0,
+        },
+    ];
+    check(libc::syscall(libc::SYS_capset, &header, data.as_ptr()) as i32)
+}
+
+/// The pid 1 of the sandbox: reaps whatever gets orphaned to it, and exits with the player's
+/// status once the player is done, which kills anything left in the namespace. It can't be
+/// killed by its own signals, so a player killed by signal N is relayed as exit code 128 + N.
+unsafe fn init(player: libc::pid_t) -> ! {
+    libc::syscall(libc::SYS_close_range, 0, libc::c_uint::MAX, 0);
+    let mut status = 0;
+    loop {
+        match libc::waitpid(-1, &mut status, 0) {
+            r if r == player => break,
+            -1 if *libc::__errno_location() == libc::EINTR => continue,
+            -1 => libc::_exit(127),
+            _ => continue,
+        }
+    }
+    if libc::WIFSIGNALED(status) {
+        libc::_exit(128 + libc::WTERMSIG(status));
+    }
+    libc::_exit(libc::WEXITSTATUS(status))
+}
+
+/// Waits for the sandboxed process and exits the same way it did.
+unsafe fn supervise(pid: libc::pid_t) -> ! {
+    // Drops the stdio ends and std's exec error pipe, so the driver sees EOF on those as
+    // soon as the grandchild is done with them.
+    libc::syscall(libc::SYS_close_range, 0, libc::c_uint::MAX, 0);
+    let no_core = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    libc::setrlimit(libc::RLIMIT_CORE, &no_core);
+
+    let mut status = 0;
+    loop {
+        match libc::waitpid(pid, &mut status, 0) {
+            r if r == pid => break,
+            -1 if *libc::__errno_location() == libc::EINTR => continue,
+            _ => libc::_exit(127),
+        }
+    }
+    if libc::WIFEXITED(status) {
+        libc::_exit(libc::WEXITSTATUS(status));
+    }
+    if libc::WIFSIGNALED(status) {
+        let signal = libc::WTERMSIG(status);
+        libc::signal(signal, libc::SIG_DFL);
+        libc::kill(libc::getpid(), signal);
+        libc::_exit(128 + signal);
+    }
+    libc::_exit(127)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs, io::Read, os::unix::fs::MetadataExt, path::Path};
+
+    use nix::unistd::getuid;
+
+    use super::Sandbox;
+    use crate::{
+        config::{SandboxConfig, SandboxImage},
+        container::{ContainerBackend, ContainerSpec, ResourceLimits},
+    };
+
+    /// A cgroup the sandbox can put its leaves under, when the host has cgroup v2 with every
+    /// controller it needs and the tests may write there.
+    fn cgroup_root() -> Option<String> {
+        let controllers = fs::read_to_string("/sys/fs/cgroup/cgroup.controllers").ok()?;
+        let controllers: Vec<_> = controllers.split_whitespace().collect();
+        if !["memory", "cpu", "pids"]
+            .iter()
+            .all(|c| controllers.contains(c))
+        {
+            return None;
+        }
+        let root = format!("/sys/fs/cgroup/cc-driver-test-{}", std::process::id());
+        fs::create_dir(&root).ok()?;
+        Some(root)
+    }
+
+    #[test]
+    fn isolates_the_process_and_keeps_the_rootfs_untouched() {
+        // A merged /usr stands in for an image, with bin and lib where an image's / has them
+        if !Path::new("/usr/lib64").is_dir() || !Path::new("/usr/bin/sh").exists() {
+            eprintln!("Skipping, the sandbox test runs the host's /usr as its image");
+            return;
+        }
+        let Some(cgroup_root) = cgroup_root() else {
+            eprintln!("Skipping, the sandbox needs a writable cgroup v2 hierarchy");
+            return;
+        };
+        let dir = std::env::temp_dir().join(format!("cc-driver-sandbox-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let image = SandboxImage {
+            rootfs: "/usr".to_owned(),
+            entrypoint: vec!["/bin/sh".to_owned(), "-c".to_owned()],
+            workdir: "/".to_owned(),
+            env: BTreeMap::new(),
+        };
+        let sandbox = Sandbox::new(SandboxConfig {
+            cgroup_root: cgroup_root.to_owned(),
+            pids_limit: 16,
+            seccomp: true,
+            uid: 65534,
+            gid: 65534,
+            images: BTreeMap::from([("host".to_owned(), image)]),
+        });
+        let limits = ResourceLimits {
+            memory: "64m".to_owned(),
+            cpus: 0.5,
+            cpu_time: 5,
+        };
+        let marker = format!("/cc-driver-sandbox-{}", std::process::id());
+        let script = format!(
+            "echo $$ > /data/pid; cat /proc/self/uid_map > /data/uid_map; \
+             grep -c : /proc/net/dev > /data/interfaces; \
+             : > {marker} && : > /tmp/scratch && echo written > /data/out; sleep 1"
+        );
+        let spec = ContainerSpec::new("isolated", "host", limits)
+            .arg(script)
+            .mount(dir.to_string_lossy(), "/data");
+
+        let mut child = sandbox.spawn(spec).unwrap();
+        let leaf = Path::new(&cgroup_root).join("isolated");
+        let read = |file: &str| fs::read_to_string(leaf.join(file)).unwrap();
+        assert_eq!(read("memory.max").trim(), (64 << 20).to_string());
+        assert_eq!(read("cpu.max").trim(), "50000 100000");
+        assert_eq!(read("pids.max").trim(), "16");
+        assert!(!read("cgroup.procs").trim().is_empty());
+
+        let status = child.wait().unwrap();
+        let mut stderr = String::new();
+        child
+            .stderr
+            .take()
+            .unwrap()
+            .read_to_string(&mut stderr)
+            .unwrap();
+        sandbox.finish(child.id());
+        assert!(status.success(), "{}", stderr);
+
+        // Runs as pid 2 under the sandbox's init, as root of its own user namespace, with
+        // only a loopback interface
+        let read = |file: &str| fs::read_to_string(dir.join(file)).unwrap();
+        assert_eq!(read("pid").trim(), "2");
+        assert!(read("uid_map").split_whitespace().next() == Some("0"));
+        assert_eq!(read("interfaces").trim(), "1");
+        assert_eq!(read("out").trim(), "written");
+        assert!(!Path::new("/usr").join(&marker[1..]).exists());
+        if getuid().is_root() {
+            assert_eq!(fs::metadata(dir.join("out")).unwrap().uid(), 65534);
+        }
+        assert!(!leaf.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+        let _ = fs::remove_dir(&cgroup_root);
+    }
+}
+
+mod seccomp {
+    use std::io;
+
+    use nix::libc;
+
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JEQ_K: u16 = 0x15;
+    const BPF_JGE_K: u16 = 0x35;
+    const BPF_JSET_K: u16 = 0x45;
+    const BPF_RET_K: u16 = 0x06;
+
+    const SECCOMP_DATA_NR: u32 = 0;
+    const SECCOMP_DATA_ARCH: u32 = 4;
+    /// The low half of the first argument, on little endian.
+    const SECCOMP_DATA_ARG0: u32 = 16;
+    const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    /// Flags that would let `clone` create namespaces to escape the sandbox's.
+    const CLONE_NAMESPACES: u32 = (libc::CLONE_NEWNS
+        | libc::CLONE_NEWCGROUP
+        | libc::CLONE_NEWUTS
+        | libc::CLONE_NEWIPC
+        | libc::CLONE_NEWUSER
+        | libc::CLONE_NEWPID
+        | libc::CLONE_NEWNET) as u32;
+
+    /// Enough for the C++, Java and Python runtimes besides `clone`, which is checked for
+    /// namespace flags. Anything else fails with `EPERM`.
+    #[cfg(target_arch = "x86_64")]
+    const ALLOWED_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_readv,
+        libc::SYS_writev,
+        libc::SYS_pread64,
+        libc::SYS_pwrite64,
+        libc::SYS_open,
+        libc::SYS_openat,
+        libc::SYS_close,
+        libc::SYS_close_range,
+        libc::SYS_stat,
+        libc::SYS_fstat,
+        libc::SYS_lstat,
+        libc::SYS_newfstatat,
+        libc::SYS_statx,
+        libc::SYS_statfs,
+        libc::SYS_fstatfs,
+        libc::SYS_lseek,
+        libc::SYS_access,
+        libc::SYS_faccessat,
+        libc::SYS_faccessat2,
+        libc::SYS_readlink,
+        libc::SYS_readlinkat,
+        libc::SYS_getdents64,
+        libc::SYS_getcwd,
+        libc::SYS_chdir,
+        libc::SYS_fcntl,
+        libc::SYS_flock,
+        libc::SYS_ioctl,
+        libc::SYS_dup,
+        libc::SYS_dup2,
+        libc::SYS_dup3,
+        libc::SYS_pipe,
+        libc::SYS_pipe2,
+        libc::SYS_mkdir,
+        libc::SYS_mkdirat,
+        libc::SYS_unlink,
+        libc::SYS_unlinkat,
+        libc::SYS_rename,
+        libc::SYS_ftruncate,
+        libc::SYS_fsync,
+        libc::SYS_fdatasync,
+        libc::SYS_mmap,
+        libc::SYS_mprotect,
+        libc::SYS_munmap,
+        libc::SYS_mremap,
+        libc::SYS_madvise,
+        libc::SYS_brk,
+        libc::SYS_membarrier,
+        libc::SYS_rt_sigaction,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_sigaltstack,
+        libc::SYS_tgkill,
+        libc::SYS_select,
+        libc::SYS_pselect6,
+        libc::SYS_poll,
+        libc::SYS_ppoll,
+        libc::SYS_epoll_create1,
+        libc::SYS_epoll_ctl,
+        libc::SYS_epoll_wait,
+        libc::SYS_epoll_pwait,
+        libc::SYS_eventfd2,
+        libc::SYS_futex,
+        libc::SYS_set_robust_list,
+        libc::SYS_get_robust_list,
+        libc::SYS_set_tid_address,
+        libc::SYS_rseq,
+        libc::SYS_fork,
+        libc::SYS_vfork,
+        libc::SYS_execve,
+        libc::SYS_wait4,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+        libc::SYS_sched_yield,
+        libc::SYS_sched_getaffinity,
+        libc::SYS_sched_getparam,
+        libc::SYS_sched_getscheduler,
+        libc::SYS_nanosleep,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_clock_gettime,
+        libc::SYS_clock_getres,
+        libc::SYS_gettimeofday,
+        libc::SYS_getpid,
+        libc::SYS_getppid,
+        libc::SYS_gettid,
+        libc::SYS_getuid,
+        libc::SYS_geteuid,
+        libc::SYS_getgid,
+        libc::SYS_getegid,
+        libc::SYS_getrlimit,
+        libc::SYS_prlimit64,
+        libc::SYS_getrusage,
+        libc::SYS_sysinfo,
+        libc::SYS_uname,
+        libc::SYS_arch_prctl,
+        libc::SYS_prctl,
+        libc::SYS_getrandom,
+    ];
+
+    fn stmt(code: u16, k: u32) -> libc::sock_filter {
+        libc::sock_filter {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+        libc::sock_filter { code, jt, jf, k }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn filter() -> io::Result<Vec<libc::sock_filter>> {
+        let mut prog = vec![
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+            jump(BPF_JEQ_K, AUDIT_ARCH_X86_64, 1, 0),
+            stmt(BPF_RET_K, libc::SECCOMP_RET_KILL_PROCESS),
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+            // x32 syscalls would bypass the allowlist below
+            jump(BPF_JGE_K, X32_SYSCALL_BIT, 0, 1),
+            stmt(BPF_RET_K, libc::SECCOMP_RET_KILL_PROCESS),
+            // clone3's flags are behind a pointer seccomp can't follow, so the C libraries
+            // are made to fall back to clone
+            jump(BPF_JEQ_K, libc::SYS_clone3 as u32, 0, 1),
+            stmt(BPF_RET_K, libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32),
+            jump(BPF_JEQ_K, libc::SYS_clone as u32, 0, 4),
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARG0),
+            jump(BPF_JSET_K, CLONE_NAMESPACES, 0, 1),
+            stmt(BPF_RET_K, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32),
+            stmt(BPF_RET_K, libc::SECCOMP_RET_ALLOW),
+        ];
+        for nr in ALLOWED_SYSCALLS {
+            prog.push(jump(BPF_JEQ_K, *nr as u32, 0, 1));
+            prog.push(stmt(BPF_RET_K, libc::SECCOMP_RET_ALLOW));
+        }
+        prog.push(stmt(
+            BPF_RET_K,
+            libc::SECCOMP_RET_ERRNO | libc::EPERM as u32,
+        ));
+        Ok(prog)
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn filter() -> io::Result<Vec<libc::sock_filter>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the seccomp allowlist is only available on x86_64, set SANDBOX_SECCOMP=false",
+        ))
+    }
+
+    #[cfg(all(test, target_arch = "x86_64"))]
+    mod tests {
+        use nix::libc;
+
+        use super::{filter, ALLOWED_SYSCALLS};
+
+        #[test]
+        fn every_allowed_syscall_has_a_check() {
+            let prog = filter().unwrap();
+            assert_eq!(prog.len(), 13 + 2 * ALLOWED_SYSCALLS.len() + 1);
+            assert!(prog.len() <= libc::BPF_MAXINSNS as usize);
+            assert_eq!(
+                prog.last().unwrap().k,
+                libc::SECCOMP_RET_ERRNO | libc::EPERM as u32
+            );
+        }
+    }
+}
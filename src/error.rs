@@ -1,4 +1,4 @@
-#[derive(Debug,Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SimulatorError {
     CompilationError(String),
     RuntimeError(String),
@@ -6,9 +6,27 @@ pub enum SimulatorError {
     FifoCreationError(String),
     EpollError(String),
     TimeOutError(String),
+    MemoryLimitError(String),
     RabbitMqError(String),
     Player1Error(String),
     Player2Error(String),
+    /// Another request for the same game is being played already, which publishes its result.
+    GameAlreadyRunning(String),
+    /// The request can't be played as it is, e.g. its game id can't name a directory.
+    InvalidRequest(String),
+}
+
+impl SimulatorError {
+    /// Failures of the driver or its host rather than of the submitted code, worth retrying.
+    pub fn is_infrastructure(&self) -> bool {
+        matches!(
+            self,
+            SimulatorError::UnidentifiedError(_)
+                | SimulatorError::FifoCreationError(_)
+                | SimulatorError::EpollError(_)
+                | SimulatorError::RabbitMqError(_)
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -1,19 +1,97 @@
+use std::{collections::BTreeSet, io, path::Path, sync::Mutex};
+
+use crate::error::SimulatorError;
+
+/// Written into every game directory with the id of the driver instance that owns it, so
+/// only our own leftovers are ever removed.
+pub const MARKER_FILE: &str = ".cc-driver";
+
+/// Written into the directories kept for inspection, which the reaper leaves alone.
+pub const KEPT_FILE: &str = ".cc-driver-kept";
+
+/// Games that currently have a directory, i.e. are being played by this process.
+static LIVE_GAMES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Whether `game_id` can name a game directory and its containers: 1 to 64 ASCII letters,
+/// digits, `-` or `_`.
+pub fn is_valid_game_id(game_id: &str) -> bool {
+    (1..=64).contains(&game_id.len())
+        && game_id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
 #[derive(Debug)]
 pub struct GameDir {
     full_path: String,
+    game_id: String,
+    keep: bool,
 }
 
 impl GameDir {
-    pub fn new(game_id: &str) -> Option<Self> {
-        let err = std::fs::create_dir(format!("/tmp/{game_id}"));
-        if let Err(_e) = err {
-            return None;
+    /// Creates `<root>/<game_id>`. A directory left behind by an earlier run of the same
+    /// instance is replaced, anything else (or the game already running) is refused.
+    pub fn new(root: &str, owner: &str, game_id: &str) -> Result<Self, SimulatorError> {
+        if !is_valid_game_id(game_id) {
+            return Err(SimulatorError::InvalidRequest(format!(
+                "Invalid game id {game_id:?}"
+            )));
+        }
+        if !LIVE_GAMES.lock().unwrap().insert(game_id.to_owned()) {
+            return Err(SimulatorError::GameAlreadyRunning(format!(
+                "Game {game_id} is already being played"
+            )));
+        }
+        let full_path = format!("{root}/{game_id}");
+        let created = match std::fs::create_dir(&full_path) {
+            Err(e)
+                if e.kind() == io::ErrorKind::AlreadyExists && is_owned_by(&full_path, owner) =>
+            {
+                std::fs::remove_dir_all(&full_path).and_then(|_| std::fs::create_dir(&full_path))
+            }
+            res => res,
         }
-        Some(GameDir {
-            full_path: format!("/tmp/{game_id}"),
+        .and_then(|_| std::fs::write(format!("{full_path}/{MARKER_FILE}"), owner));
+
+        if let Err(e) = created {
+            LIVE_GAMES.lock().unwrap().remove(game_id);
+            return Err(SimulatorError::UnidentifiedError(format!(
+                "Failed to create game directory: {e}"
+            )));
+        }
+        Ok(GameDir {
+            full_path,
+            game_id: game_id.to_owned(),
+            keep: false,
         })
     }
 
+    /// Whether to leave the directory behind for inspection once the game is over.
+    pub fn keep(mut self, keep: bool) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    pub fn is_live(game_id: &str) -> bool {
+        LIVE_GAMES.lock().unwrap().contains(game_id)
+    }
+
+    /// Removes the directories under `root` owned by `owner` whose game isn't running and that
+    /// weren't kept, and returns how many there were.
+    pub fn remove_stale(root: &str, owner: &str) -> io::Result<usize> {
+        let mut removed = 0;
+        for entry in std::fs::read_dir(root)?.flatten() {
+            let path = entry.path();
+            let game_id = entry.file_name().to_string_lossy().into_owned();
+            let kept = path.join(KEPT_FILE).exists();
+            if is_owned_by(&path, owner) && !kept && !GameDir::is_live(&game_id) {
+                std::fs::remove_dir_all(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
     pub fn create_sub_dir(&self, sub_dir: &str) -> Option<()> {
         std::fs::create_dir_all(format!("{}/{}", self.full_path, sub_dir)).ok()
     }
@@ -22,9 +100,20 @@ impl GameDir {
         &self.full_path
     }
 }
+
+fn is_owned_by(dir: impl AsRef<Path>, owner: &str) -> bool {
+    std::fs::read_to_string(dir.as_ref().join(MARKER_FILE)).is_ok_and(|o| o == owner)
+}
+
 impl Drop for GameDir {
     fn drop(&mut self) {
-        let _ = std::fs::remove_dir_all(self.get_path());
+        if self.keep {
+            log::info!("Keeping the game directory {}", self.get_path());
+            let _ = std::fs::write(format!("{}/{KEPT_FILE}", self.get_path()), "");
+        } else {
+            let _ = std::fs::remove_dir_all(self.get_path());
+        }
+        LIVE_GAMES.lock().unwrap().remove(&self.game_id);
     }
 }
 
@@ -33,11 +122,12 @@ mod tests {
     use std::{io::Write, path::Path};
 
     use super::GameDir;
+    use crate::error::SimulatorError;
 
     #[test]
     fn dir_creation_and_deletion_check() {
         let game_id = "030af985-f4b5-4914-94d8-e559576449e3";
-        let match_dir_handle = GameDir::new(game_id).unwrap();
+        let match_dir_handle = GameDir::new("/tmp", "test", game_id).unwrap();
 
         let full_path = match_dir_handle.get_path().to_owned();
 
@@ -52,4 +142,61 @@ mod tests {
 
         assert!(!Path::new(&full_path).exists());
     }
+
+    #[test]
+    fn stale_dirs_are_reused_and_swept() {
+        let root = format!("/tmp/cc-driver-game-dir-test-{}", std::process::id());
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(format!("{root}/foreign")).unwrap();
+
+        let live = GameDir::new(&root, "test", "live").unwrap();
+        assert!(matches!(
+            GameDir::new(&root, "test", "live"),
+            Err(SimulatorError::GameAlreadyRunning(_))
+        ));
+        assert!(GameDir::new(&root, "test", "foreign")
+            .unwrap_err()
+            .is_infrastructure());
+
+        // What a crashed driver leaves behind
+        let stale = GameDir::new(&root, "test", "stale").unwrap();
+        std::mem::forget(stale);
+        super::LIVE_GAMES.lock().unwrap().remove("stale");
+        std::fs::write(format!("{root}/stale/leftover"), "").unwrap();
+
+        let reused = GameDir::new(&root, "test", "stale").unwrap();
+        assert!(!Path::new(&format!("{root}/stale/leftover")).exists());
+        std::mem::forget(reused);
+        super::LIVE_GAMES.lock().unwrap().remove("stale");
+
+        let kept = GameDir::new(&root, "test", "kept").unwrap().keep(true);
+        drop(kept);
+
+        assert_eq!(GameDir::remove_stale(&root, "other").unwrap(), 0);
+        assert_eq!(GameDir::remove_stale(&root, "test").unwrap(), 1);
+        assert!(Path::new(live.get_path()).exists());
+        assert!(Path::new(&format!("{root}/kept")).exists());
+        assert!(Path::new(&format!("{root}/foreign")).exists());
+
+        drop(live);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn game_ids_must_be_a_single_safe_name() {
+        for game_id in [
+            "../../x",
+            "a/b",
+            "",
+            "gäme",
+            "game\r\nX-Injected: 1",
+            &"a".repeat(65),
+        ] {
+            assert!(matches!(
+                GameDir::new("/tmp", "test", game_id),
+                Err(SimulatorError::InvalidRequest(_))
+            ));
+        }
+        assert!(super::is_valid_game_id("Game_1-2"));
+    }
 }
@@ -1,38 +1,107 @@
-use std::env;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use log::info;
 use nix::sys::epoll::EpollFlags;
 
 use crate::{
+    bus::Report,
+    config::DriverConfig,
+    container::{self, ContainerBackend},
     create_final_pvp_response, create_final_response, create_normal_error_response,
-    create_pvp_error_response,
+    create_pvp_error_response, create_validation_response,
     error::SimulatorError,
     fifo::Fifo,
     game_dir::GameDir,
     poll::{
         epoll::{CallbackMessage, EpollGeneric},
-        epoll_entry::{EpollEntryType, Process, ProcessOutput, ProcessType},
+        epoll_entry::{EpollEntryType, Process, ProcessOutput, ProcessType, Verdict},
+    },
+    request::{
+        GameRequest, Language, NormalGameRequest, PlayerCode, PvPGameRequest, ValidationRequest,
     },
-    request::{GameRequest, Language, NormalGameRequest, PlayerCode, PvPGameRequest},
-    response::GameStatus,
-    runner::{cpp, java, py, simulator, GameType, Runnable},
+    response::{GameProgress, GameStage, GameStatus, ResourceUsage, Resources},
+    runner::{cpp, generic, java, py, simulator, GameType, Runnable},
     utils::{copy_files, send_initial_input, send_initial_pvp_input},
 };
 
-pub trait Handler {
-    fn handle(self) -> GameStatus;
+pub trait Handler: Sized {
+    fn handle(self, config: &DriverConfig) -> GameStatus {
+        self.handle_reporting(config, &Report::default())
+    }
+
+    /// Plays the game, calling `report` as it reaches each [`GameStage`] after the first.
+    fn handle_reporting(self, config: &DriverConfig, report: &Report) -> GameStatus;
+}
+
+type HandledEvents = (Vec<Option<ProcessOutput>>, Vec<(ProcessType, Verdict)>);
+
+type Usage = Vec<(ProcessType, ResourceUsage)>;
+
+/// Kills and reaps every process still registered, leaving their stderr entries in place and
+/// adding what they used to `usage`. Returns the ones that had already failed on their own, and
+/// how they died.
+fn kill_processes(
+    epoll_handle: &mut EpollGeneric<EpollEntryType>,
+    usage: &mut Usage,
+) -> Vec<(ProcessType, Verdict)> {
+    let killable_processes = epoll_handle
+        .get_registered_fds()
+        .iter()
+        .filter_map(|x| match x.1 {
+            EpollEntryType::Process(_) => Some(*x.0),
+            _ => None,
+        })
+        .collect::<Vec<u64>>();
+    killable_processes
+        .iter()
+        .filter_map(|x| match epoll_handle.unregister(*x).unwrap() {
+            EpollEntryType::Process(mut p) => {
+                p.kill();
+                let status = p.wait().ok()?;
+                usage.extend(p.usage().map(|u| (*p.get_type(), u)));
+                p.verdict(status).map(|verdict| (*p.get_type(), verdict))
+            }
+            EpollEntryType::StdErr(_) => unreachable!(),
+        })
+        .collect()
 }
 
+/// Unregisters the remaining stderr entries, keeping whatever they have written so far.
+fn drain_outputs(epoll_handle: &mut EpollGeneric<EpollEntryType>) -> Vec<ProcessOutput> {
+    let fds = epoll_handle
+        .get_registered_fds()
+        .keys()
+        .copied()
+        .collect::<Vec<u64>>();
+    fds.into_iter()
+        .filter_map(|fd| match epoll_handle.unregister(fd).ok()? {
+            EpollEntryType::StdErr(mut output) => {
+                let _ = output.read_to_string();
+                Some(output)
+            }
+            EpollEntryType::Process(_) => None,
+        })
+        .collect()
+}
+
+fn player_output(outputs: Vec<ProcessOutput>, process_type: ProcessType) -> String {
+    outputs
+        .into_iter()
+        .filter(|x| x.process_type() == &process_type)
+        .map(|x| x.output())
+        .collect::<String>()
+}
+
+/// Handles what happened within `timeout`, adding what the processes that exited used to `usage`.
 fn handle_event(
     epoll_handle: &mut EpollGeneric<EpollEntryType>,
-) -> Result<(Vec<Option<ProcessOutput>>, Vec<Option<ProcessType>>), SimulatorError> {
-    let events = epoll_handle.poll(
-        env::var("EPOLL_WAIT_TIMEOUT")
-            .unwrap()
-            .parse()
-            .unwrap_or(1000),
-        epoll_handle.get_registered_fds().len(),
-    )?;
+    timeout: isize,
+    usage: &mut Usage,
+) -> Result<HandledEvents, SimulatorError> {
+    let events = epoll_handle.poll(timeout, epoll_handle.get_registered_fds().len())?;
     let mut res = vec![];
     let mut errors = vec![];
     for e in events {
@@ -52,39 +121,23 @@ fn handle_event(
                     EpollEntryType::StdErr(_) => unreachable!(),
                     EpollEntryType::Process(mut p) => {
                         let exit_status = p.wait()?;
-                        if exit_status.success() {
-                            res.push(None);
-                        } else {
-                            let killable_processes = epoll_handle
-                                .get_registered_fds()
-                                .iter()
-                                .filter_map(|x| match x.1 {
-                                    EpollEntryType::Process(_) => Some(*x.0),
-                                    _ => None,
-                                })
-                                .collect::<Vec<u64>>();
-                            killable_processes.iter().for_each(|x| {
-                                match epoll_handle.unregister(*x).unwrap() {
-                                    EpollEntryType::Process(mut p) => p.kill(),
-                                    EpollEntryType::StdErr(_) => unreachable!(),
-                                }
-                            });
-                            match p.get_type() {
-                                &ProcessType::Runner => errors.push(Some(ProcessType::Runner)),
-                                &ProcessType::RunnerPlayer1 => {
-                                    errors.push(Some(ProcessType::RunnerPlayer1))
-                                }
-                                &ProcessType::RunnerPlayer2 => {
-                                    errors.push(Some(ProcessType::RunnerPlayer2))
-                                }
-                                &ProcessType::Simulator => {
-                                    errors.push(Some(ProcessType::Simulator))
-                                }
+                        usage.extend(p.usage().map(|u| (*p.get_type(), u)));
+                        match p.verdict(exit_status) {
+                            None => res.push(None),
+                            Some(verdict) => {
+                                errors.push((*p.get_type(), verdict));
+                                errors.extend(kill_processes(epoll_handle, usage));
                             }
                         }
                     }
                 }
             }
+            CallbackMessage::DeadlineExceeded => {
+                kill_processes(epoll_handle, usage);
+                return Err(SimulatorError::TimeOutError(
+                    "Game exceeded the time limit".to_owned(),
+                ));
+            }
             CallbackMessage::Nop => {
                 res.push(None);
             }
@@ -93,51 +146,97 @@ fn handle_event(
     Ok((res, errors))
 }
 
+/// What the player of `player_type` and the simulator used, if the backend could tell.
+fn resources(usage: &Usage, player_type: ProcessType, compile_time_ms: u64) -> Resources {
+    let usage_of = |process_type| {
+        usage
+            .iter()
+            .find(|(p, _)| *p == process_type)
+            .map(|(_, u)| *u)
+    };
+    Resources {
+        compile_time_ms,
+        player: usage_of(player_type),
+        simulator: usage_of(ProcessType::Simulator),
+    }
+}
+
+/// How long the player took to start since `started`, which is mostly compiling it.
+fn compile_time(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+/// Follows the simulator's log and reports the progress after each turn, at most once every
+/// `interval`.
+fn watch_progress(
+    mut progress: GameProgress,
+    interval: Duration,
+    report: Report,
+) -> impl FnMut(&str) {
+    let mut last_report: Option<Instant> = None;
+    move |ln| {
+        if let Some(turn) = progress.update(ln) {
+            if last_report.is_none_or(|at| at.elapsed() >= interval) {
+                last_report = Some(Instant::now());
+                report.progress(turn);
+            }
+        }
+    }
+}
+
 fn get_runner(
     player_code: &PlayerCode,
-    game_id: &String,
+    game_id: &str,
     game_dir_handle: &GameDir,
-    player_dir: &String,
+    player_dir: &str,
+    backend: &Arc<dyn ContainerBackend>,
 ) -> Box<dyn Runnable> {
-    match player_code.language {
+    match &player_code.language {
         Language::CPP => Box::new(cpp::Runner::new(
             game_dir_handle.get_path().to_string(),
             game_id.to_string(),
             player_dir.to_owned(),
+            Arc::clone(backend),
         )),
         Language::PYTHON => Box::new(py::Runner::new(
             game_dir_handle.get_path().to_string(),
             game_id.to_string(),
             player_dir.to_owned(),
+            Arc::clone(backend),
         )),
         Language::JAVA => Box::new(java::Runner::new(
             game_dir_handle.get_path().to_string(),
             game_id.to_string(),
             player_dir.to_owned(),
+            Arc::clone(backend),
+        )),
+        Language::Other(language) => Box::new(generic::Runner::new(
+            game_dir_handle.get_path().to_string(),
+            game_id.to_string(),
+            player_dir.to_owned(),
+            language.to_owned(),
+            Arc::clone(backend),
         )),
     }
 }
 
 impl Handler for NormalGameRequest {
-    fn handle(self) -> GameStatus {
+    fn handle_reporting(self, config: &DriverConfig, report: &Report) -> GameStatus {
         info!(
             "Starting normal game execution for {} with language {:?}",
             self.game_id, self.player_code.language
         );
-        let game_dir_handle = GameDir::new(&self.game_id);
-
-        if game_dir_handle.is_none() {
-            return create_normal_error_response(
-                self.game_id,
-                SimulatorError::UnidentifiedError("Failed to create game directory".to_owned()),
-            );
-        }
-
-        let game_dir_handle = game_dir_handle.unwrap();
+        let game_dir_handle =
+            match GameDir::new(&config.game_dir_root, &config.instance_id, &self.game_id) {
+                Ok(dir) => dir.keep(config.keep_game_dirs),
+                Err(e) => return create_normal_error_response(self.game_id, e),
+            };
+        let backend = container::from_config(config);
         let player_dir = "player".to_string();
         game_dir_handle.create_sub_dir(&player_dir);
 
         if let Some(resp) = copy_files(
+            config,
             &self.game_id,
             &self.player_code,
             &game_dir_handle,
@@ -158,28 +257,51 @@ impl Handler for NormalGameRequest {
                 let (p1_stdin, p2_stdout) = p1.get_ends().unwrap();
                 let (p2_stdin, p1_stdout) = p2.get_ends().unwrap();
 
-                send_initial_input(vec![&p1_stdout, &p2_stdout], &self);
+                send_initial_input(vec![&p1_stdout, &p2_stdout], &self, config.map_size);
 
                 let runner = get_runner(
                     &self.player_code,
                     &self.game_id,
                     &game_dir_handle,
                     &player_dir,
+                    &backend,
                 );
 
+                // Kept when compiling fails as well
+                let mut compile_time_ms = 0;
                 let initialize = || -> Result<_, SimulatorError> {
-                    let mut player_process =
-                        runner.run(p1_stdin, p1_stdout, GameType::NormalGame)?;
-                    let simulator = simulator::Simulator::new(self.game_id.to_string());
-                    let mut sim_process = simulator.run(p2_stdin, p2_stdout)?;
+                    let started = Instant::now();
+                    let player_process =
+                        runner.run(config, p1_stdin, p1_stdout, GameType::NormalGame, report);
+                    compile_time_ms = compile_time(started);
+                    let mut player_process = player_process?;
+                    report.stage(GameStage::RUNNING);
+                    let simulator = simulator::Simulator::new(
+                        self.game_id.to_string(),
+                        game_dir_handle.get_path().to_string(),
+                        Arc::clone(&backend),
+                    );
+                    let mut sim_process = simulator.run(config, p2_stdin, p2_stdout)?;
 
                     let player_stderr = player_process.stderr.take().unwrap();
                     let sim_stderr = sim_process.stderr.take().unwrap();
 
-                    let player_process = Process::new(player_process, ProcessType::Runner);
-                    let sim_process = Process::new(sim_process, ProcessType::Simulator);
-                    let player_output = ProcessOutput::new(player_stderr, ProcessType::Runner);
-                    let sim_output = ProcessOutput::new(sim_stderr, ProcessType::Simulator);
+                    let player_process =
+                        Process::new(player_process, ProcessType::Runner, Arc::clone(&backend));
+                    let sim_process =
+                        Process::new(sim_process, ProcessType::Simulator, Arc::clone(&backend));
+                    let player_output =
+                        ProcessOutput::new(player_stderr, ProcessType::Runner, config.max_log_size);
+                    let sim_output =
+                        ProcessOutput::new(sim_stderr, ProcessType::Simulator, config.max_log_size)
+                            .on_line(watch_progress(
+                                GameProgress::new(
+                                    self.game_id.to_owned(),
+                                    self.parameters.no_of_coins,
+                                ),
+                                Duration::from_millis(config.progress_interval),
+                                report.clone(),
+                            ));
 
                     let player = EpollEntryType::Process(player_process);
                     let player_output = EpollEntryType::StdErr(player_output);
@@ -188,6 +310,9 @@ impl Handler for NormalGameRequest {
 
                     let mut event_handler =
                         EpollGeneric::<EpollEntryType>::new().map_err(SimulatorError::from)?;
+                    event_handler
+                        .set_deadline(Duration::from_secs(config.game_time_limit))
+                        .map_err(SimulatorError::from)?;
 
                     event_handler
                         .register(player, EpollFlags::EPOLLIN | EpollFlags::EPOLLHUP)
@@ -206,37 +331,62 @@ impl Handler for NormalGameRequest {
                 };
 
                 let mut event_handler = match initialize() {
-                    Ok(handler) => handler,
-                    Err(err) => return create_normal_error_response(self.game_id, err),
+                    Ok(event_handler) => event_handler,
+                    Err(err) => {
+                        return create_normal_error_response(self.game_id, err).with_resources(
+                            resources(&vec![], ProcessType::Runner, compile_time_ms),
+                        )
+                    }
                 };
 
                 let mut outputs: Vec<ProcessOutput> = vec![];
+                let mut usage = vec![];
 
                 while !event_handler.is_empty() {
-                    match handle_event(&mut event_handler) {
+                    match handle_event(&mut event_handler, config.epoll_wait_timeout, &mut usage) {
                         Ok((result, errors)) => {
                             outputs.extend(result.into_iter().flatten());
-                            if !errors.is_empty() {
-                                while !outputs.is_empty() {
-                                    let process = outputs.remove(0);
-                                    match process.process_type() {
-                                        ProcessType::Runner => {
-                                            return create_normal_error_response(
-                                                self.game_id,
-                                                SimulatorError::RuntimeError(process.output()),
-                                            )
-                                        }
-                                        _ => {
-                                            return create_normal_error_response(self.game_id, SimulatorError::RuntimeError(format!("couldnt communicate with simulator check syntax \n {}", process.output())));
-                                        }
+                            // A player that died takes the simulator down with it
+                            let failed = errors
+                                .iter()
+                                .find(|(p, _)| *p == ProcessType::Runner)
+                                .or(errors.first());
+                            if let Some(&(process_type, verdict)) = failed {
+                                outputs.extend(drain_outputs(&mut event_handler));
+                                let error = match process_type {
+                                    ProcessType::Runner => {
+                                        verdict.error(player_output(outputs, ProcessType::Runner))
                                     }
-                                }
+                                    _ => SimulatorError::RuntimeError(format!(
+                                        "couldnt communicate with simulator check syntax \n {}",
+                                        player_output(outputs, ProcessType::Simulator)
+                                    )),
+                                };
+                                return create_normal_error_response(self.game_id, error)
+                                    .with_resources(resources(
+                                        &usage,
+                                        ProcessType::Runner,
+                                        compile_time_ms,
+                                    ));
                             }
                         }
-                        Err(_) => {
+                        Err(SimulatorError::TimeOutError(reason)) => {
+                            outputs.extend(drain_outputs(&mut event_handler));
+                            let log = player_output(outputs, ProcessType::Runner);
                             return create_normal_error_response(
                                 self.game_id,
-                                SimulatorError::RuntimeError("Unknown error occured".to_owned()),
+                                SimulatorError::TimeOutError(format!("{reason}\n{log}")),
+                            )
+                            .with_resources(resources(
+                                &usage,
+                                ProcessType::Runner,
+                                compile_time_ms,
+                            ));
+                        }
+                        Err(err) => {
+                            kill_processes(&mut event_handler, &mut usage);
+                            return create_normal_error_response(self.game_id, err).with_resources(
+                                resources(&usage, ProcessType::Runner, compile_time_ms),
                             );
                         }
                     }
@@ -263,6 +413,11 @@ impl Handler for NormalGameRequest {
                     player_process_out,
                     sim_process_out,
                 )
+                .with_resources(resources(
+                    &usage,
+                    ProcessType::Runner,
+                    compile_time_ms,
+                ))
             }
 
             (Err(e), _) | (_, Err(e)) => create_normal_error_response(self.game_id, e),
@@ -271,24 +426,17 @@ impl Handler for NormalGameRequest {
 }
 
 impl Handler for PvPGameRequest {
-    fn handle(self) -> GameStatus {
+    fn handle_reporting(self, config: &DriverConfig, report: &Report) -> GameStatus {
         info!(
             "Starting pvp game execution for {} with languages player1: {:?} and player2: {:?}",
             self.game_id, self.player1.language, self.player2.language
         );
-        let game_dir_handle = GameDir::new(&self.game_id);
-
-        if game_dir_handle.is_none() {
-            return create_pvp_error_response(
-                self.game_id,
-                SimulatorError::UnidentifiedError("Failed to create game directory".to_owned()),
-                SimulatorError::UnidentifiedError("Failed to create game directory".to_owned()),
-                true,
-                true,
-            );
-        }
-
-        let game_dir_handle = game_dir_handle.unwrap();
+        let game_dir_handle =
+            match GameDir::new(&config.game_dir_root, &config.instance_id, &self.game_id) {
+                Ok(dir) => dir.keep(config.keep_game_dirs),
+                Err(e) => return create_pvp_error_response(self.game_id, e.clone(), e, true, true),
+            };
+        let backend = container::from_config(config);
         let player1_dir = "pvp_game/player_1";
         let player2_dir = "pvp_game/player_2";
 
@@ -298,20 +446,22 @@ impl Handler for PvPGameRequest {
         //print all files in game_dir_handle
 
         if let Some(resp) = copy_files(
+            config,
             &self.game_id,
             &self.player1,
             &game_dir_handle,
-            &player1_dir.to_string(),
+            player1_dir,
             &GameType::PvPGame,
         ) {
             return resp;
         }
 
         if let Some(resp) = copy_files(
+            config,
             &self.game_id,
             &self.player2,
             &game_dir_handle,
-            &player2_dir.to_string(),
+            player2_dir,
             &GameType::PvPGame,
         ) {
             return resp;
@@ -343,51 +493,88 @@ impl Handler for PvPGameRequest {
                     &self.player1,
                     &self.game_id,
                     &game_dir_handle,
-                    &player1_dir.to_string(),
+                    player1_dir,
+                    &backend,
                 );
                 let runner2 = get_runner(
                     &self.player2,
                     &self.game_id,
                     &game_dir_handle,
-                    &player2_dir.to_string(),
+                    player2_dir,
+                    &backend,
                 );
+                // Kept when compiling fails as well
+                let (mut compile_time1, mut compile_time2) = (0, 0);
                 let initialize = || -> Result<_, SimulatorError> {
-                    let mut player1_process =
-                        runner1
-                            .run(p1_r, p1_w, GameType::PvPGame)
-                            .map_err(|e| match e {
-                                SimulatorError::CompilationError(e) => {
-                                    SimulatorError::Player1Error(e)
-                                }
-                                _ => SimulatorError::Player1Error("Couldnt compile".to_owned()),
-                            })?;
-                    let mut player2_process =
-                        runner2
-                            .run(p2_r, p2_w, GameType::PvPGame)
-                            .map_err(|e| match e {
-                                SimulatorError::CompilationError(e) => {
-                                    SimulatorError::Player2Error(e)
-                                }
-                                _ => SimulatorError::Player2Error("Couldnt compile".to_owned()),
-                            })?;
-                    let simulator = simulator::Simulator::new(self.game_id.to_string());
+                    let started = Instant::now();
+                    let player1_process =
+                        runner1.run(config, p1_r, p1_w, GameType::PvPGame, report);
+                    compile_time1 = compile_time(started);
+                    let mut player1_process = player1_process.map_err(|e| match e {
+                        SimulatorError::CompilationError(e) => SimulatorError::Player1Error(e),
+                        // Not the player's fault, e.g. the container couldn't be spawned
+                        e => e,
+                    })?;
+                    let started = Instant::now();
+                    let player2_process =
+                        runner2.run(config, p2_r, p2_w, GameType::PvPGame, report);
+                    compile_time2 = compile_time(started);
+                    let mut player2_process = player2_process.map_err(|e| match e {
+                        SimulatorError::CompilationError(e) => SimulatorError::Player2Error(e),
+                        e => e,
+                    })?;
+                    report.stage(GameStage::RUNNING);
+                    let simulator = simulator::Simulator::new(
+                        self.game_id.to_string(),
+                        game_dir_handle.get_path().to_string(),
+                        Arc::clone(&backend),
+                    );
                     let mut sim_process =
-                        simulator.run_pvp(sim_r, sim_w, p1_in, p3_in, p2_in, p4_in)?;
+                        simulator.run_pvp(config, sim_r, sim_w, p1_in, p3_in, p2_in, p4_in)?;
 
                     let player1_stderr = player1_process.stderr.take().unwrap();
                     let player2_stderr = player2_process.stderr.take().unwrap();
 
                     let sim_stderr = sim_process.stderr.take().unwrap();
 
-                    let player1_process = Process::new(player1_process, ProcessType::RunnerPlayer1);
-                    let player2_process = Process::new(player2_process, ProcessType::RunnerPlayer2);
-                    let sim_process = Process::new(sim_process, ProcessType::Simulator);
+                    let player1_process = Process::new(
+                        player1_process,
+                        ProcessType::RunnerPlayer1,
+                        Arc::clone(&backend),
+                    );
+                    let player2_process = Process::new(
+                        player2_process,
+                        ProcessType::RunnerPlayer2,
+                        Arc::clone(&backend),
+                    );
+                    let sim_process =
+                        Process::new(sim_process, ProcessType::Simulator, Arc::clone(&backend));
 
-                    let player1_output =
-                        ProcessOutput::new(player1_stderr, ProcessType::RunnerPlayer1);
-                    let player2_output =
-                        ProcessOutput::new(player2_stderr, ProcessType::RunnerPlayer2);
-                    let sim_output = ProcessOutput::new(sim_stderr, ProcessType::Simulator);
+                    let player1_output = ProcessOutput::new(
+                        player1_stderr,
+                        ProcessType::RunnerPlayer1,
+                        config.max_log_size,
+                    );
+                    let player2_output = ProcessOutput::new(
+                        player2_stderr,
+                        ProcessType::RunnerPlayer2,
+                        config.max_log_size,
+                    );
+                    // Player 2's half of the log repeats the turns, so only player 1's is followed
+                    let mut watch = watch_progress(
+                        GameProgress::new(self.game_id.to_owned(), self.parameters.no_of_coins),
+                        Duration::from_millis(config.progress_interval),
+                        report.clone(),
+                    );
+                    let mut delimited = false;
+                    let sim_output =
+                        ProcessOutput::new(sim_stderr, ProcessType::Simulator, config.max_log_size)
+                            .on_line(move |ln| {
+                                delimited |= ln.trim().starts_with("DELIMITER");
+                                if !delimited {
+                                    watch(ln);
+                                }
+                            });
 
                     let player1 = EpollEntryType::Process(player1_process);
                     let player2 = EpollEntryType::Process(player2_process);
@@ -399,6 +586,9 @@ impl Handler for PvPGameRequest {
 
                     let mut event_handler =
                         EpollGeneric::<EpollEntryType>::new().map_err(SimulatorError::from)?;
+                    event_handler
+                        .set_deadline(Duration::from_secs(config.game_time_limit))
+                        .map_err(SimulatorError::from)?;
 
                     event_handler
                         .register(player1, EpollFlags::EPOLLIN | EpollFlags::EPOLLHUP)
@@ -421,96 +611,122 @@ impl Handler for PvPGameRequest {
                     Ok(event_handler)
                 };
 
-                let mut event_handler = match initialize() {
-                    Ok(handler) => handler,
-                    Err(err) => match err {
-                        SimulatorError::Player1Error(error) => {
-                            return create_pvp_error_response(
+                let event_handler = initialize();
+                let with_resources = |status: GameStatus, usage: &Usage| {
+                    status.with_pvp_resources(
+                        resources(usage, ProcessType::RunnerPlayer1, compile_time1),
+                        resources(usage, ProcessType::RunnerPlayer2, compile_time2),
+                    )
+                };
+                let mut event_handler = match event_handler {
+                    Ok(event_handler) => event_handler,
+                    Err(err) => {
+                        let status = match err {
+                            SimulatorError::Player1Error(error) => create_pvp_error_response(
                                 self.game_id,
-                                SimulatorError::CompilationError(format!("{error}")),
+                                SimulatorError::CompilationError(error),
                                 SimulatorError::CompilationError(
                                     "Other player couldnt compile".to_owned(),
                                 ),
                                 true,
                                 false,
-                            )
-                        }
-                        SimulatorError::Player2Error(error) => {
-                            return create_pvp_error_response(
+                            ),
+                            SimulatorError::Player2Error(error) => create_pvp_error_response(
                                 self.game_id,
                                 SimulatorError::CompilationError(
                                     "Other player couldnt compile".to_owned(),
                                 ),
-                                SimulatorError::CompilationError(format!("{error}")),
+                                SimulatorError::CompilationError(error),
                                 false,
                                 true,
-                            )
-                        }
-                        _ => {
-                            return create_pvp_error_response(
+                            ),
+                            _ => create_pvp_error_response(
                                 self.game_id.to_owned(),
                                 err.clone(),
                                 err,
                                 true,
                                 true,
-                            )
-                        }
-                    },
+                            ),
+                        };
+                        return with_resources(status, &vec![]);
+                    }
                 };
 
                 let mut outputs: Vec<ProcessOutput> = vec![];
-                let mut all_errors: Vec<ProcessType> = vec![];
+                let mut all_errors: Vec<(ProcessType, Verdict)> = vec![];
+                let mut usage = vec![];
 
                 while !event_handler.is_empty() {
-                    match handle_event(&mut event_handler) {
+                    match handle_event(&mut event_handler, config.epoll_wait_timeout, &mut usage) {
                         Ok((result, errors)) => {
                             outputs.extend(result.into_iter().flatten());
-                            all_errors.extend(errors.into_iter().flatten());
+                            all_errors.extend(errors);
                         }
-                        Err(_) => {
-                            // Handle the case where handle_event returns an error
-                            // Adjust the error message as needed
-                            return create_pvp_error_response(
+                        Err(SimulatorError::TimeOutError(reason)) => {
+                            outputs.extend(drain_outputs(&mut event_handler));
+                            let (player1_outputs, player2_outputs) = outputs
+                                .into_iter()
+                                .partition(|x| x.process_type() == &ProcessType::RunnerPlayer1);
+                            let status = create_pvp_error_response(
                                 self.game_id,
-                                SimulatorError::RuntimeError("Unknown runtime error".to_owned()),
-                                SimulatorError::RuntimeError("Unknown runtime error".to_owned()),
+                                SimulatorError::TimeOutError(format!(
+                                    "{reason}\n{}",
+                                    player_output(player1_outputs, ProcessType::RunnerPlayer1)
+                                )),
+                                SimulatorError::TimeOutError(format!(
+                                    "{reason}\n{}",
+                                    player_output(player2_outputs, ProcessType::RunnerPlayer2)
+                                )),
                                 true,
                                 true,
                             );
+                            return with_resources(status, &usage);
+                        }
+                        Err(err) => {
+                            kill_processes(&mut event_handler, &mut usage);
+                            let status = create_pvp_error_response(
+                                self.game_id,
+                                err.clone(),
+                                err,
+                                true,
+                                true,
+                            );
+                            return with_resources(status, &usage);
                         }
                     }
                 }
 
-                if all_errors.contains(&ProcessType::RunnerPlayer1) {
+                let player1_resources =
+                    resources(&usage, ProcessType::RunnerPlayer1, compile_time1);
+                let player2_resources =
+                    resources(&usage, ProcessType::RunnerPlayer2, compile_time2);
+                let verdict = |process_type| {
+                    all_errors
+                        .iter()
+                        .find(|(p, _)| *p == process_type)
+                        .map(|(_, verdict)| *verdict)
+                };
+
+                if let Some(verdict) = verdict(ProcessType::RunnerPlayer1) {
                     return create_pvp_error_response(
                         self.game_id,
-                        SimulatorError::RuntimeError(
-                            outputs
-                                .into_iter()
-                                .filter(|x| x.process_type() == &ProcessType::RunnerPlayer1)
-                                .map(|x| x.output())
-                                .collect::<String>(),
-                        ),
+                        verdict.error(player_output(outputs, ProcessType::RunnerPlayer1)),
                         SimulatorError::RuntimeError("the other player threw an error".to_owned()),
                         true,
                         false,
-                    );
+                    )
+                    .with_pvp_resources(player1_resources, player2_resources);
                 }
 
-                if all_errors.contains(&ProcessType::RunnerPlayer2) {
+                if let Some(verdict) = verdict(ProcessType::RunnerPlayer2) {
                     return create_pvp_error_response(
                         self.game_id,
                         SimulatorError::RuntimeError("the other player threw an error".to_owned()),
-                        SimulatorError::RuntimeError(
-                            outputs
-                                .into_iter()
-                                .filter(|x| x.process_type() == &ProcessType::RunnerPlayer2)
-                                .map(|x| x.output())
-                                .collect::<String>(),
-                        ),
+                        verdict.error(player_output(outputs, ProcessType::RunnerPlayer2)),
                         false,
                         true,
-                    );
+                    )
+                    .with_pvp_resources(player1_resources, player2_resources);
                 }
 
                 let process1 = outputs.remove(0);
@@ -570,6 +786,7 @@ impl Handler for PvPGameRequest {
                     player2_process_out,
                     sim_process_out,
                 )
+                .with_pvp_resources(player1_resources, player2_resources)
             }
 
             (Err(e), _, _, _, _)
@@ -583,11 +800,54 @@ impl Handler for PvPGameRequest {
     }
 }
 
+impl Handler for ValidationRequest {
+    fn handle_reporting(self, config: &DriverConfig, report: &Report) -> GameStatus {
+        info!(
+            "Validating the {:?} code of {}",
+            self.player_code.language, self.game_id
+        );
+        let game_dir_handle =
+            match GameDir::new(&config.game_dir_root, &config.instance_id, &self.game_id) {
+                Ok(dir) => dir.keep(config.keep_game_dirs),
+                Err(e) => return create_validation_response(self.game_id, 0, Err(e)),
+            };
+        let backend = container::from_config(config);
+        let player_dir = "player";
+        game_dir_handle.create_sub_dir(player_dir);
+
+        if let Some(resp) = copy_files(
+            config,
+            &self.game_id,
+            &self.player_code,
+            &game_dir_handle,
+            player_dir,
+            &GameType::NormalGame,
+        ) {
+            let err = resp.failure.unwrap_or_else(|| {
+                SimulatorError::UnidentifiedError("Failed to copy the player's code".to_owned())
+            });
+            return create_validation_response(self.game_id, 0, Err(err));
+        }
+
+        let runner = get_runner(
+            &self.player_code,
+            &self.game_id,
+            &game_dir_handle,
+            player_dir,
+            &backend,
+        );
+        let started = Instant::now();
+        let result = runner.compile(config, &GameType::NormalGame, report);
+        create_validation_response(self.game_id, compile_time(started), result)
+    }
+}
+
 impl Handler for GameRequest {
-    fn handle(self) -> GameStatus {
+    fn handle_reporting(self, config: &DriverConfig, report: &Report) -> GameStatus {
         match self {
-            GameRequest::NormalGame(request) => request.handle(),
-            GameRequest::PvPGame(request) => request.handle(),
+            GameRequest::NormalGame(request) => request.handle_reporting(config, report),
+            GameRequest::PvPGame(request) => request.handle_reporting(config, report),
+            GameRequest::Validation(request) => request.handle_reporting(config, report),
         }
     }
 }
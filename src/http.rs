@@ -0,0 +1,421 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Read,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use serde::de::DeserializeOwned;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    bus::{Job, RequestSource, ResponseSink, Ticket},
+    config::DriverConfig,
+    create_stage_response,
+    error::SimulatorError,
+    game_dir::is_valid_game_id,
+    request::{GameRequest, NormalGameRequest, PvPGameRequest, ValidationRequest},
+    response::{GameStage, GameStatus, GameStatusEnum},
+    workers::Workers,
+};
+
+/// Results of finished games kept for polling, oldest are forgotten first.
+const FINISHED_GAMES: usize = 1000;
+/// Requests handled at once, more are turned away until one is done.
+const MAX_HANDLERS: usize = 64;
+/// Clients waiting on their game at once, fewer than [`MAX_HANDLERS`] so polling still works.
+const MAX_WAITERS: usize = 32;
+
+/// How many of a limited number of slots are taken.
+struct Slots {
+    taken: AtomicUsize,
+    limit: usize,
+}
+
+/// A taken slot, freed when dropped.
+struct Slot(Arc<Slots>);
+
+impl Slots {
+    fn new(limit: usize) -> Arc<Self> {
+        Arc::new(Slots {
+            taken: AtomicUsize::new(0),
+            limit,
+        })
+    }
+
+    /// Takes a slot, unless they are all taken.
+    fn take(self: &Arc<Self>) -> Option<Slot> {
+        self.taken
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |taken| {
+                (taken < self.limit).then_some(taken + 1)
+            })
+            .ok()
+            .map(|_| Slot(Arc::clone(self)))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.taken.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[derive(Default)]
+struct Games {
+    /// The latest status of each game as JSON, and whether it is the final one.
+    statuses: HashMap<String, (String, bool)>,
+    /// Finished games, oldest first.
+    finished: VecDeque<String>,
+    /// Set on shutdown, when games still queued won't be played anymore.
+    closed: bool,
+}
+
+/// The statuses of the games submitted over HTTP, for clients to poll or wait on.
+#[derive(Default)]
+pub struct GameStore {
+    games: Mutex<Games>,
+    changed: Condvar,
+}
+
+impl GameStore {
+    /// The latest status of `game_id` as JSON, and whether it is the final one.
+    pub fn get(&self, game_id: &str) -> Option<(String, bool)> {
+        self.games.lock().unwrap().statuses.get(game_id).cloned()
+    }
+
+    /// Records the game as submitted, unless it is already being played.
+    fn submit(&self, status: GameStatus) -> bool {
+        let mut games = self.games.lock().unwrap();
+        if matches!(games.statuses.get(&status.game_id), Some((_, false))) {
+            return false;
+        }
+        games.finished.retain(|id| *id != status.game_id);
+        let json = serde_json::to_string(&status).unwrap();
+        games.statuses.insert(status.game_id, (json, false));
+        true
+    }
+
+    /// Forgets a game that couldn't be queued.
+    fn withdraw(&self, game_id: &str) {
+        self.games.lock().unwrap().statuses.remove(game_id);
+    }
+
+    /// Blocks until `game_id` finished and returns its final status, or `None` if the driver
+    /// shut down first.
+    fn wait(&self, game_id: &str) -> Option<String> {
+        let mut games = self.games.lock().unwrap();
+        loop {
+            match games.statuses.get(game_id) {
+                Some((json, true)) => return Some(json.to_owned()),
+                Some(_) if !games.closed => games = self.changed.wait(games).unwrap(),
+                _ => return None,
+            }
+        }
+    }
+
+    fn close(&self) {
+        self.games.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+}
+
+impl ResponseSink for GameStore {
+    fn publish(&self, response: GameStatus) -> Result<(), SimulatorError> {
+        let finished = matches!(
+            response.game_status,
+            GameStatusEnum::EXECUTED | GameStatusEnum::EXECUTE_ERROR
+        );
+        let json = serde_json::to_string(&response).map_err(|e| {
+            SimulatorError::UnidentifiedError(format!("Unable to serialize the response: {e}"))
+        })?;
+        let mut games = self.games.lock().unwrap();
+        games
+            .statuses
+            .insert(response.game_id.to_owned(), (json, finished));
+        if finished {
+            games.finished.push_back(response.game_id);
+            while games.finished.len() > FINISHED_GAMES {
+                if let Some(id) = games.finished.pop_front() {
+                    games.statuses.remove(&id);
+                }
+            }
+        }
+        self.changed.notify_all();
+        Ok(())
+    }
+}
+
+fn respond(request: Request, status: u16, body: String) {
+    respond_with(request, status, body, None)
+}
+
+fn respond_with(request: Request, status: u16, body: String, location: Option<String>) {
+    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let mut response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header);
+    if let Some(Ok(location)) = location.map(|l| Header::from_bytes("Location", l)) {
+        response.add_header(location);
+    }
+    if let Err(e) = request.respond(response) {
+        log::warn!("Unable to respond to an HTTP request: {e}");
+    }
+}
+
+fn error(request: Request, status: u16, message: &str) {
+    respond(
+        request,
+        status,
+        serde_json::json!({ "error": message }).to_string(),
+    );
+}
+
+/// Reads the request as a `T`, or the status and message to reply with.
+fn parse<T: DeserializeOwned + Into<GameRequest>>(
+    request: &mut Request,
+    max_bytes: u64,
+) -> Result<GameRequest, (u16, String)> {
+    let too_large = || (413, format!("Requests can take at most {max_bytes} bytes"));
+    if request
+        .body_length()
+        .is_some_and(|length| length as u64 > max_bytes)
+    {
+        return Err(too_large());
+    }
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(max_bytes + 1)
+        .read_to_string(&mut body)
+        .map_err(|e| (400, format!("Unable to read the request: {e}")))?;
+    if body.len() as u64 > max_bytes {
+        return Err(too_large());
+    }
+    let game: GameRequest = serde_json::from_str::<T>(&body)
+        .map(Into::into)
+        .map_err(|e| (400, format!("Invalid game request: {e}")))?;
+    if !is_valid_game_id(game.game_id()) {
+        return Err((
+            400,
+            "game_id must be 1 to 64 letters, digits, - or _".to_owned(),
+        ));
+    }
+    Ok(game)
+}
+
+fn submit(
+    request: Request,
+    game: Result<GameRequest, (u16, String)>,
+    pool: Sender<Job>,
+    store: Arc<GameStore>,
+    waiters: Arc<Slots>,
+) {
+    let game = match game {
+        Ok(game) => game,
+        Err((status, e)) => return error(request, status, &e),
+    };
+    let wait = request
+        .url()
+        .split_once('?')
+        .is_some_and(|(_, query)| query.split('&').any(|p| p == "wait=true"));
+    // Taken before the game is queued, so a client turned away doesn't leave it behind
+    let waiting = match wait.then(|| waiters.take()) {
+        Some(None) => {
+            return error(
+                request,
+                503,
+                "Too many clients are waiting, poll the game instead",
+            )
+        }
+        waiting => waiting.flatten(),
+    };
+    let game_id = game.game_id().to_owned();
+    if !store.submit(create_stage_response(&game_id, GameStage::QUEUED)) {
+        return error(request, 409, "The game is already being played");
+    }
+    let job = Job {
+        request: game,
+        ticket: Ticket::detached(),
+        sink: Arc::clone(&store) as Arc<dyn ResponseSink>,
+    };
+    match pool.try_send(job) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => {
+            store.withdraw(&game_id);
+            return error(request, 503, "Every worker is busy, try again later");
+        }
+        Err(TrySendError::Disconnected(_)) => {
+            store.withdraw(&game_id);
+            return error(request, 503, "The driver is shutting down");
+        }
+    }
+    // Not holding on to the pool while waiting, so shutting down isn't held up
+    drop(pool);
+
+    if let Some(_waiting) = waiting {
+        return match store.wait(&game_id) {
+            Some(json) => respond(request, 200, json),
+            None => error(
+                request,
+                503,
+                "The driver shut down before the game finished",
+            ),
+        };
+    }
+    let (json, _) = store.get(&game_id).unwrap_or_default();
+    respond_with(request, 202, json, Some(format!("/games/{game_id}")));
+}
+
+fn route(
+    mut request: Request,
+    normal: Sender<Job>,
+    pvp: Sender<Job>,
+    store: Arc<GameStore>,
+    waiters: Arc<Slots>,
+    max_bytes: u64,
+) {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_owned();
+    let segments: Vec<_> = path.trim_matches('/').split('/').collect();
+    match (request.method(), segments.as_slice()) {
+        (Method::Post, ["games", "normal"]) => {
+            drop(pvp);
+            let game = parse::<NormalGameRequest>(&mut request, max_bytes);
+            submit(request, game, normal, store, waiters)
+        }
+        (Method::Post, ["games", "pvp"]) => {
+            drop(normal);
+            let game = parse::<PvPGameRequest>(&mut request, max_bytes);
+            submit(request, game, pvp, store, waiters)
+        }
+        (Method::Post, ["games", "validation"]) => {
+            drop(pvp);
+            let game = parse::<ValidationRequest>(&mut request, max_bytes);
+            submit(request, game, normal, store, waiters)
+        }
+        (Method::Get, ["games", game_id]) => match store.get(game_id) {
+            Some((json, _)) => respond(request, 200, json),
+            None => error(request, 404, "No such game"),
+        },
+        (_, ["games", ..]) => error(request, 405, "Method not allowed"),
+        _ => error(request, 404, "Not found"),
+    }
+}
+
+/// Serves the HTTP API on `http_address`, playing the games submitted on the workers. Once
+/// stopped, queued games are dropped and running games get `shutdown_grace_period` seconds
+/// to finish.
+pub struct Api {
+    config: Arc<DriverConfig>,
+}
+
+impl Api {
+    pub fn new(config: Arc<DriverConfig>) -> Self {
+        Api { config }
+    }
+}
+
+impl RequestSource for Api {
+    fn run(self: Box<Self>, workers: Workers, stop: Receiver<()>) -> Result<(), SimulatorError> {
+        serve(&self.config, workers, stop)
+    }
+}
+
+fn serve(
+    config: &DriverConfig,
+    workers: Workers,
+    stop: Receiver<()>,
+) -> Result<(), SimulatorError> {
+    let server = Server::http(&config.http_address).map_err(|e| {
+        SimulatorError::UnidentifiedError(format!(
+            "Unable to listen on {}: {e}",
+            config.http_address
+        ))
+    })?;
+    let server = Arc::new(server);
+    log::info!("Listening on {}", config.http_address);
+    let unblock = Arc::clone(&server);
+    thread::spawn(move || {
+        let _ = stop.recv();
+        unblock.unblock();
+    });
+
+    let store = Arc::new(GameStore::default());
+    let (handlers, waiters) = (Slots::new(MAX_HANDLERS), Slots::new(MAX_WAITERS));
+    let max_bytes = config.http_max_request_size << 10;
+    for request in server.incoming_requests() {
+        let Some(handling) = handlers.take() else {
+            error(request, 503, "Too many requests at once, try again later");
+            continue;
+        };
+        let (normal, pvp) = (workers.normal(), workers.pvp());
+        let (store, waiters) = (Arc::clone(&store), Arc::clone(&waiters));
+        thread::spawn(move || {
+            let _handling = handling;
+            route(request, normal, pvp, store, waiters, max_bytes)
+        });
+    }
+
+    let grace_period = Duration::from_secs(config.shutdown_grace_period);
+    if workers.finish(Instant::now() + grace_period) {
+        log::info!("Every running game finished");
+    } else {
+        log::warn!("Games still running after the grace period were abandoned");
+    }
+    store.close();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use super::{GameStore, Slots};
+    use crate::{
+        bus::ResponseSink,
+        response::{GameStatus, GameStatusEnum},
+    };
+
+    fn status(game_status: GameStatusEnum) -> GameStatus {
+        GameStatus::new_normal("game".to_owned(), game_status, None)
+    }
+
+    #[test]
+    fn games_are_played_once_at_a_time() {
+        let store = Arc::new(GameStore::default());
+        assert!(store.submit(status(GameStatusEnum::IDLE)));
+        assert!(!store.submit(status(GameStatusEnum::IDLE)));
+
+        let waiter = {
+            let store = Arc::clone(&store);
+            thread::spawn(move || store.wait("game"))
+        };
+        store.publish(status(GameStatusEnum::EXECUTING)).unwrap();
+        assert_eq!(store.get("game").map(|(_, finished)| finished), Some(false));
+        store.publish(status(GameStatusEnum::EXECUTED)).unwrap();
+
+        let json = waiter.join().unwrap().unwrap();
+        assert!(json.contains("EXECUTED"));
+        assert!(store.submit(status(GameStatusEnum::IDLE)));
+    }
+
+    #[test]
+    fn slots_are_freed_when_dropped() {
+        let slots = Slots::new(2);
+        let first = slots.take().unwrap();
+        let _second = slots.take().unwrap();
+        assert!(slots.take().is_none());
+
+        drop(first);
+        assert!(slots.take().is_some());
+    }
+}
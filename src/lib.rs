@@ -1,19 +1,34 @@
 #![feature(linux_pidfd)]
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use error::SimulatorError;
 use log::error;
-use response::{GameResult, GameResultPvP, GameStatusEnum};
+use response::{
+    CompilationResult, Diagnostic, ErrorCode, GameError, GameResult, GameResultPvP, GameStage,
+    GameStatusEnum,
+};
+pub mod bus;
+pub mod cache;
+pub mod config;
+pub mod container;
 pub mod error;
 pub mod fifo;
 pub mod game_dir;
 pub mod handlers;
+pub mod http;
 pub mod mq;
 pub mod poll;
+pub mod reaper;
 pub mod request;
 pub mod response;
 pub mod runner;
+pub mod shutdown;
+pub mod submission;
 pub mod utils;
+pub mod workers;
 
 fn get_turnwise_logs(player_log: String) -> HashMap<usize, Vec<String>> {
     let mut turnwise_logs = HashMap::new();
@@ -123,11 +138,15 @@ pub fn create_final_pvp_response(
             score: player1_score,
             has_errors: false,
             log: player1_final_logs,
+            error: None,
+            resources: None,
         }),
         Some(GameResultPvP {
             score: player2_score,
             has_errors: false,
             log: player2_final_logs,
+            error: None,
+            resources: None,
         }),
     )
 }
@@ -194,119 +213,179 @@ pub fn create_final_response(
             coins_used: (parameters.no_of_coins - coins_left) as u64,
             has_errors: false,
             log: final_logs,
+            error: None,
+            resources: None,
         }),
     )
 }
 
-pub fn create_pvp_error_response(
-    game_id: String,
-    err_message_p1: SimulatorError,
-    err_message_p2: SimulatorError,
-    runner1_error: bool,
-    runner2_error: bool,
-) -> response::GameStatus {
-    error!("Error in execution: p1 error {:?} p2 error {:?} ", err_message_p1, err_message_p2);
-    let (err_typep1, errorp1) = match err_message_p1 {
-        SimulatorError::RuntimeError(e) => ("Runtime Error!".to_owned(), e),
-        SimulatorError::CompilationError(e) => ("Compilation Error!".to_owned(), e),
-        SimulatorError::FifoCreationError(e) => ("Process Communication Error!".to_owned(), e),
-        SimulatorError::UnidentifiedError(e) => {
-            ("Unidentified Error. Contact the POCs!".to_owned(), e)
+fn game_error(err: SimulatorError) -> GameError {
+    let (code, message, details) = match err {
+        SimulatorError::RuntimeError(e) => (ErrorCode::RUNTIME_ERROR, "Runtime Error!", e),
+        SimulatorError::CompilationError(e) => {
+            (ErrorCode::COMPILATION_ERROR, "Compilation Error!", e)
         }
-        SimulatorError::TimeOutError(e) => ("Timeout Error!".to_owned(), e),
-        SimulatorError::EpollError(e) => ("Event Creation Error!".to_owned(), e),
-        SimulatorError::RabbitMqError(e) => ("RabbitMq Error!".to_owned(), e),
-        SimulatorError::Player1Error(e) => ("Player1 Error!".to_owned(), e),
-        SimulatorError::Player2Error(e) => ("Player2 Error!".to_owned(), e),
-    };
-    let (err_typep2, errorp2) = match err_message_p2 {
-        SimulatorError::RuntimeError(e) => ("Runtime Error!".to_owned(), e),
-        SimulatorError::CompilationError(e) => ("Compilation Error!".to_owned(), e),
-        SimulatorError::FifoCreationError(e) => ("Process Communication Error!".to_owned(), e),
-        SimulatorError::UnidentifiedError(e) => {
-            ("Unidentified Error. Contact the POCs!".to_owned(), e)
+        SimulatorError::FifoCreationError(e) => {
+            (ErrorCode::INTERNAL, "Process Communication Error!", e)
+        }
+        SimulatorError::UnidentifiedError(e) => (
+            ErrorCode::INTERNAL,
+            "Unidentified Error. Contact the POCs!",
+            e,
+        ),
+        SimulatorError::TimeOutError(e) => (ErrorCode::TIME_LIMIT, "Timeout Error!", e),
+        SimulatorError::MemoryLimitError(e) => {
+            (ErrorCode::MEMORY_LIMIT, "Memory Limit Exceeded!", e)
         }
-        SimulatorError::TimeOutError(e) => ("Timeout Error!".to_owned(), e),
-        SimulatorError::EpollError(e) => ("Event Creation Error!".to_owned(), e),
-        SimulatorError::RabbitMqError(e) => ("RabbitMq Error!".to_owned(), e),
-        SimulatorError::Player1Error(e) => ("Player1 Error!".to_owned(), e),
-        SimulatorError::Player2Error(e) => ("Player2 Error!".to_owned(), e),
+        SimulatorError::EpollError(e) => (ErrorCode::INTERNAL, "Event Creation Error!", e),
+        SimulatorError::RabbitMqError(e) => (ErrorCode::INTERNAL, "RabbitMq Error!", e),
+        SimulatorError::Player1Error(e) => (ErrorCode::COMPILATION_ERROR, "Player1 Error!", e),
+        SimulatorError::Player2Error(e) => (ErrorCode::COMPILATION_ERROR, "Player2 Error!", e),
+        SimulatorError::GameAlreadyRunning(e) => (ErrorCode::INTERNAL, "Game Already Running!", e),
+        SimulatorError::InvalidRequest(e) => (ErrorCode::INTERNAL, "Invalid Request!", e),
     };
+    GameError {
+        code,
+        message: message.to_owned(),
+        details,
+    }
+}
 
-    let errorp1 = errorp1
-        .lines()
-        .map(|x| format!("ERRORS, {x}"))
-        .collect::<Vec<String>>()
-        .join("\n");
-
-    let errorp2 = errorp2
+/// The error as `ERRORS, ...` log lines, for clients that don't read `error` yet.
+fn error_log(error: &GameError) -> String {
+    let details = error
+        .details
         .lines()
         .map(|x| format!("ERRORS, {x}"))
         .collect::<Vec<String>>()
         .join("\n");
+    format!(
+        "ERRORS, ERROR TYPE: {}\nERRORS, ERROR LOG:\n{details}\n",
+        error.message
+    )
+}
 
-
-    response::GameStatus::new_pvp(
+pub fn create_pvp_error_response(
+    game_id: String,
+    err_message_p1: SimulatorError,
+    err_message_p2: SimulatorError,
+    runner1_error: bool,
+    runner2_error: bool,
+) -> response::GameStatus {
+    error!(
+        "Error in execution: p1 error {:?} p2 error {:?} ",
+        err_message_p1, err_message_p2
+    );
+    let failure = if err_message_p2.is_infrastructure() && !err_message_p1.is_infrastructure() {
+        err_message_p2.clone()
+    } else {
+        err_message_p1.clone()
+    };
+    let error1 = game_error(err_message_p1);
+    let error2 = game_error(err_message_p2);
+    // The log still says why the game stopped, but only the player at fault gets an error
+    let mut status = response::GameStatus::new_pvp(
         game_id,
         response::GameStatusEnum::EXECUTE_ERROR,
         Some(GameResultPvP {
             score: 0,
             has_errors: runner1_error,
-            log: format!("ERRORS, ERROR TYPE: {err_typep1}\nERRORS, ERROR LOG:\n{errorp1}\n"),
+            log: error_log(&error1),
+            error: runner1_error.then_some(error1),
+            resources: None,
         }),
         Some(GameResultPvP {
             score: 0,
             has_errors: runner2_error,
-            log: format!("ERRORS, ERROR TYPE: {err_typep2}\nERRORS, ERROR LOG:\n{errorp2}\n"),
+            log: error_log(&error2),
+            error: runner2_error.then_some(error2),
+            resources: None,
         }),
-    )
+    );
+    status.failure = Some(failure);
+    status
 }
 
 pub fn create_executing_response(game_id: &String) -> response::GameStatus {
     response::GameStatus::new_normal(game_id.to_owned(), GameStatusEnum::EXECUTING, None)
 }
 
-pub fn create_normal_error_response(game_id: String, err: SimulatorError) -> response::GameStatus {
-    error!("Error in execution: {:?}", err);
-    let (err_type, error) = match err {
-        SimulatorError::RuntimeError(e) => ("Runtime Error!".to_owned(), e),
-        SimulatorError::CompilationError(e) => ("Compilation Error!".to_owned(), e),
-        SimulatorError::FifoCreationError(e) => ("Process Communication Error!".to_owned(), e),
-        SimulatorError::UnidentifiedError(e) => {
-            ("Unidentified Error. Contact the POCs!".to_owned(), e)
+/// An update on the stage `game_id` reached, stamped with the current time.
+pub fn create_stage_response(game_id: &str, stage: GameStage) -> response::GameStatus {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64);
+    response::GameStatus {
+        stage: Some(stage),
+        timestamp: Some(timestamp),
+        ..response::GameStatus::new_normal(game_id.to_owned(), stage.game_status(), None)
+    }
+}
+
+/// The outcome of compiling the code of a validation request, which took `compile_time_ms`.
+/// Code that doesn't compile still makes for an `EXECUTED` validation.
+pub fn create_validation_response(
+    game_id: String,
+    compile_time_ms: u64,
+    result: Result<(), SimulatorError>,
+) -> response::GameStatus {
+    let (game_status, diagnostics, error) = match result {
+        Ok(()) => (GameStatusEnum::EXECUTED, vec![], None),
+        Err(SimulatorError::CompilationError(output)) => (
+            GameStatusEnum::EXECUTED,
+            Diagnostic::parse(&output),
+            Some(SimulatorError::CompilationError(output)),
+        ),
+        Err(err) => {
+            error!("Error in validation: {:?}", err);
+            (GameStatusEnum::EXECUTE_ERROR, vec![], Some(err))
         }
-        SimulatorError::TimeOutError(e) => ("Timeout Error!".to_owned(), e),
-        SimulatorError::EpollError(e) => ("Event Creation Error!".to_owned(), e),
-        SimulatorError::RabbitMqError(e) => ("RabbitMq Error!".to_owned(), e),
-        SimulatorError::Player1Error(e) => ("Player1 Error!".to_owned(), e),
-        SimulatorError::Player2Error(e) => ("Player2 Error!".to_owned(), e),
     };
+    let mut status = response::GameStatus::new_validation(
+        game_id,
+        game_status,
+        Some(CompilationResult {
+            success: error.is_none(),
+            diagnostics,
+            compile_time_ms,
+            error: error.clone().map(game_error),
+        }),
+    );
+    if status.game_status == GameStatusEnum::EXECUTE_ERROR {
+        status.failure = error;
+    }
+    status
+}
 
-    let error = error
-        .lines()
-        .map(|x| format!("ERRORS, {x}"))
-        .collect::<Vec<String>>()
-        .join("\n");
-
-    response::GameStatus::new_normal(
+pub fn create_normal_error_response(game_id: String, err: SimulatorError) -> response::GameStatus {
+    error!("Error in execution: {:?}", err);
+    let failure = err.clone();
+    let error = game_error(err);
+    let mut status = response::GameStatus::new_normal(
         game_id,
         response::GameStatusEnum::EXECUTE_ERROR,
         Some(response::GameResult {
             destruction_percentage: 0.0,
             coins_used: 0,
             has_errors: true,
-            log: format!("ERRORS, ERROR TYPE: {err_type}\nERRORS, ERROR LOG:\n{error}\n"),
+            log: error_log(&error),
+            error: Some(error),
+            resources: None,
         }),
-    )
+    );
+    status.failure = Some(failure);
+    status
 }
 
 #[cfg(test)]
 mod tests {
 
     use crate::{
-        create_final_response, get_turnwise_logs,
+        create_final_response, create_normal_error_response,
+        error::SimulatorError,
+        get_turnwise_logs,
         request::{GameParameters, Language, NormalGameRequest, PlayerCode},
-        response::{GameResult, GameStatus, GameStatusEnum},
+        response::{ErrorCode, GameResult, GameStatus, GameStatusEnum},
     };
 
     #[test]
@@ -375,6 +454,8 @@ mod tests {
             player_code: PlayerCode {
                 language: Language::CPP,
                 source_code: "".to_owned(),
+                files: Default::default(),
+                archive: None,
             },
             map: vec![vec![]],
         };
@@ -394,10 +475,28 @@ mod tests {
                 destruction_percentage: 75.0,
                 coins_used: (tot_coins - 10) as u64,
                 has_errors: false,
-                log: "TURN, 1\nPRINT, Bug is here\nPRINT, No it's here\nCOINS, 100\nDESTRUCTION, 20.0%\nTURN, 3\nCOINS, 100\nDESTRUCTION, 20.0%\nTURN, 100\nPRINT, Nope, it's been here the whole time\nDESTRUCTION, 75.0%\nCOINS, 10\n".to_owned()
+                log: "TURN, 1\nPRINT, Bug is here\nPRINT, No it's here\nCOINS, 100\nDESTRUCTION, 20.0%\nTURN, 3\nCOINS, 100\nDESTRUCTION, 20.0%\nTURN, 100\nPRINT, Nope, it's been here the whole time\nDESTRUCTION, 75.0%\nCOINS, 10\n".to_owned(),
+                error: None,
+                resources: None,
             })
         );
 
         assert_eq!(expected_game_status, result);
     }
+
+    #[test]
+    fn errors_are_structured_and_logged() {
+        let status = create_normal_error_response(
+            "1".to_owned(),
+            SimulatorError::CompilationError("run.cpp:1:1: error\nint main(".to_owned()),
+        );
+        let result = status.game_result.unwrap();
+        let error = result.error.unwrap();
+        assert_eq!(error.code, ErrorCode::COMPILATION_ERROR);
+        assert_eq!(error.details, "run.cpp:1:1: error\nint main(");
+        assert_eq!(
+            result.log,
+            "ERRORS, ERROR TYPE: Compilation Error!\nERRORS, ERROR LOG:\nERRORS, run.cpp:1:1: error\nERRORS, int main(\n"
+        );
+    }
 }
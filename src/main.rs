@@ -1,11 +1,18 @@
-use std::{env, sync::Arc};
+use std::{path::PathBuf, sync::Arc};
 
 use cc_driver::{
-    create_executing_response,
+    bus::RequestSource,
+    config::{DriverConfig, Frontend, Mode},
+    container,
     handlers::Handler,
-    mq::{consumer, Publisher},
-    request::GameRequest,
+    http::Api,
+    mq::Consumer,
+    reaper::Reaper,
+    request::{GameRequest, NormalGameRequest, PvPGameRequest, ValidationRequest},
+    shutdown,
+    workers::{self, Workers},
 };
+use clap::{ArgGroup, Parser, Subcommand};
 use log::{info, LevelFilter};
 use log4rs::{
     append::{
@@ -16,19 +23,99 @@ use log4rs::{
     filter::threshold::ThresholdFilter,
 };
 
-fn worker_fn(msg_receiver: crossbeam_channel::Receiver<GameRequest>, publisher: Arc<Publisher>) {
-    while let Ok(req) = msg_receiver.recv() {
-        // publishing error means we can crash, something is wrong
-        publisher
-            .publish(create_executing_response(req.game_id()))
-            .unwrap();
-        let response = req.handle();
-        publisher.publish(response).unwrap();
+#[derive(Parser)]
+#[command(about = "Plays CodeCharacter games")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Play the games requested through the configured frontend (the default)
+    Serve,
+    /// Play a single game from a request file and print its status as JSON
+    #[command(group(ArgGroup::new("request").required(true)))]
+    Run {
+        /// A normal game request
+        #[arg(long, group = "request")]
+        normal: Option<PathBuf>,
+        /// A PvP game request
+        #[arg(long, group = "request")]
+        pvp: Option<PathBuf>,
+        /// A validation request, only compiling the code
+        #[arg(long, group = "request")]
+        validate: Option<PathBuf>,
+        /// Leave the game directory behind for inspection
+        #[arg(long)]
+        keep_dir: bool,
+    },
+}
+
+fn read_request(
+    normal: Option<PathBuf>,
+    pvp: Option<PathBuf>,
+    validate: Option<PathBuf>,
+) -> Result<GameRequest, String> {
+    let (path, parse): (_, fn(&str) -> serde_json::Result<GameRequest>) =
+        match (normal, pvp, validate) {
+            (Some(path), _, _) => (path, |s| {
+                serde_json::from_str::<NormalGameRequest>(s).map(Into::into)
+            }),
+            (None, Some(path), _) => (path, |s| {
+                serde_json::from_str::<PvPGameRequest>(s).map(Into::into)
+            }),
+            (None, None, Some(path)) => (path, |s| {
+                serde_json::from_str::<ValidationRequest>(s).map(Into::into)
+            }),
+            (None, None, None) => {
+                unreachable!("clap requires one of --normal, --pvp and --validate")
+            }
+        };
+    let contents =
+        std::fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+    parse(&contents).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn run(config: DriverConfig, request: GameRequest) {
+    let status = request.handle(&config);
+    println!("{}", serde_json::to_string_pretty(&status).unwrap());
+}
+
+fn serve(config: DriverConfig) {
+    info!("Starting driver");
+    let config = Arc::new(config);
+
+    // Before any thread is spawned, so they all leave the signals to the signalfd
+    let stop = match shutdown::on_signal() {
+        Ok(stop) => stop,
+        Err(e) => {
+            log::error!("Unable to handle signals: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let reaper = Reaper::new(Arc::clone(&config), container::from_config(&config));
+    reaper.start();
+
+    let workers = Workers::start(Arc::clone(&config), workers::play);
+    let source: Box<dyn RequestSource> = match config.frontend {
+        Frontend::RabbitMq => Box::new(Consumer::new(Arc::clone(&config))),
+        Frontend::Http => Box::new(Api::new(Arc::clone(&config))),
+    };
+
+    match source.run(workers, stop) {
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("{e:?}");
+        }
     }
+    reaper.kill_all();
+    info!("Driver stopped");
 }
 
 fn main() {
-    info!("Starting driver");
+    let cli = Cli::parse();
     let level = log::LevelFilter::Info;
     let file_path = "driver.log";
 
@@ -53,18 +140,35 @@ fn main() {
 
     let _handle = log4rs::init_config(config).unwrap();
 
-    let res = consumer(
-        env::var("RABBIT_MQ_HOST").unwrap(),
-        env::var("NORMAL_GAME_REQUEST_QUEUE").unwrap(),
-        env::var("PVP_GAME_REQUEST_QUEUE").unwrap(),
-        env::var("GAME_RESPONSE_QUEUE").unwrap(),
-        worker_fn,
-    );
-
-    match res {
-        Ok(_) => {}
+    let command = cli.command.unwrap_or(Command::Serve);
+    let mode = match command {
+        Command::Serve => Mode::Serve,
+        Command::Run { .. } => Mode::Run,
+    };
+    let mut config = match DriverConfig::load(mode) {
+        Ok(config) => config,
         Err(e) => {
             log::error!("{e}");
+            std::process::exit(1);
         }
+    };
+
+    match command {
+        Command::Serve => serve(config),
+        Command::Run {
+            normal,
+            pvp,
+            validate,
+            keep_dir,
+        } => match read_request(normal, pvp, validate) {
+            Ok(request) => {
+                config.keep_game_dirs |= keep_dir;
+                run(config, request);
+            }
+            Err(e) => {
+                log::error!("{e}");
+                std::process::exit(1);
+            }
+        },
     }
 }
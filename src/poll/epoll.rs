@@ -1,8 +1,13 @@
 use std::collections::HashMap;
-use std::os::fd::RawFd;
+use std::os::fd::{AsRawFd, RawFd};
+use std::time::Duration;
 
 use nix::{
-    sys::epoll::{epoll_create, epoll_ctl, epoll_wait, EpollEvent, EpollFlags, EpollOp},
+    sys::{
+        epoll::{epoll_create, epoll_ctl, epoll_wait, EpollEvent, EpollFlags, EpollOp},
+        time::TimeSpec,
+        timerfd::{ClockId, Expiration, TimerFd, TimerFlags, TimerSetTimeFlags},
+    },
     unistd::close,
 };
 
@@ -16,6 +21,7 @@ pub trait Pollable {
 pub struct EpollGeneric<T: Pollable> {
     fds: HashMap<u64, T>,
     epoll_fd: RawFd,
+    deadline: Option<TimerFd>,
 }
 
 impl<T: Pollable> EpollGeneric<T> {
@@ -26,9 +32,31 @@ impl<T: Pollable> EpollGeneric<T> {
         Ok(EpollGeneric {
             fds: HashMap::new(),
             epoll_fd: fd,
+            deadline: None,
         })
     }
 
+    /// Arms a one shot timer, `process_event` reports `DeadlineExceeded` once it fires.
+    /// The timer is not a registered entry, so it doesn't keep `is_empty` false.
+    pub fn set_deadline(&mut self, after: Duration) -> Result<(), EpollError> {
+        let timer = TimerFd::new(
+            ClockId::CLOCK_MONOTONIC,
+            TimerFlags::TFD_NONBLOCK | TimerFlags::TFD_CLOEXEC,
+        )
+        .map_err(|e| EpollError::EpollCreateError(format!("Unable to create timer: {e}")))?;
+        timer
+            .set(
+                Expiration::OneShot(TimeSpec::from_duration(after)),
+                TimerSetTimeFlags::empty(),
+            )
+            .map_err(|e| EpollError::EpollCreateError(format!("Unable to arm timer: {e}")))?;
+        self.register_fd(timer.as_raw_fd(), EpollFlags::EPOLLIN)?;
+        if let Some(old) = self.deadline.replace(timer) {
+            self.unregister_fd(old.as_raw_fd())?;
+        }
+        Ok(())
+    }
+
     pub fn get_registered_fds(&self) -> &HashMap<u64, T> {
         &self.fds
     }
@@ -88,6 +116,11 @@ impl<T: Pollable> EpollGeneric<T> {
     }
     pub fn process_event(&mut self, event: EpollEvent) -> Result<CallbackMessage, SimulatorError> {
         let fd = event.data();
+        if let Some(timer) = &self.deadline {
+            if timer.as_raw_fd() as u64 == fd {
+                return Ok(CallbackMessage::DeadlineExceeded);
+            }
+        }
         match self.fds.get_mut(&fd) {
             Some(handle) => Ok(handle.process_event(event)?),
             None => Ok(CallbackMessage::Nop),
@@ -98,6 +131,7 @@ impl<T: Pollable> EpollGeneric<T> {
 pub enum CallbackMessage {
     Unregister(i32),
     HandleExplicitly(i32),
+    DeadlineExceeded,
     Nop,
 }
 
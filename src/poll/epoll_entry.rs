@@ -1,14 +1,23 @@
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::libc;
 use nix::sys::epoll::EpollFlags;
+use nix::sys::signal::Signal;
 
 use crate::error::EpollError;
 
-use std::env;
-use std::io::Read;
+use std::convert::TryFrom;
+use std::io::{ErrorKind, Read};
+use std::mem::MaybeUninit;
 use std::os::fd::AsRawFd;
 use std::os::linux::process::ChildExt;
+use std::os::unix::process::ExitStatusExt;
 use std::process::ChildStderr;
+use std::sync::Arc;
+use std::time::Instant;
 
+use crate::container::{ContainerBackend, ContainerExit, ContainerUsage};
 use crate::error::SimulatorError;
+use crate::response::ResourceUsage;
 
 use std::process::ExitStatus;
 
@@ -17,7 +26,7 @@ use std::process::Child;
 use super::epoll::CallbackMessage;
 use super::epoll::Pollable;
 
-#[derive(Debug,PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ProcessType {
     Runner,
     RunnerPlayer1,
@@ -25,17 +34,93 @@ pub enum ProcessType {
     Simulator,
 }
 
-#[derive(Debug)]
+/// Why a process didn't exit cleanly, the way an online judge would put it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    /// Killed by the OOM killer, as told by the backend rather than the SIGKILL it shows up as.
+    MemoryLimitExceeded,
+    /// Killed by SIGXCPU after using up the soft CPU time limit.
+    TimeLimitExceeded,
+    Signal(i32),
+    ExitCode(i32),
+}
+
+impl Verdict {
+    /// `None` for a successful exit. The docker CLI reports a container killed by signal N as
+    /// exit code 128 + N, which is read as the signal.
+    pub fn from_status(status: ExitStatus) -> Option<Self> {
+        if status.success() {
+            return None;
+        }
+        let signal = match (status.signal(), status.code()) {
+            (Some(signal), _) => signal,
+            (None, Some(code)) if code > 128 && code < 128 + 64 => code - 128,
+            (None, code) => return Some(Verdict::ExitCode(code.unwrap_or(-1))),
+        };
+        Some(match Signal::try_from(signal) {
+            Ok(Signal::SIGXCPU) => Verdict::TimeLimitExceeded,
+            _ => Verdict::Signal(signal),
+        })
+    }
+
+    /// The error for a player that stopped this way, with what it wrote to stderr.
+    pub fn error(self, log: String) -> SimulatorError {
+        match self {
+            Verdict::MemoryLimitExceeded => SimulatorError::MemoryLimitError(log),
+            Verdict::TimeLimitExceeded => SimulatorError::TimeOutError(format!("{self}\n{log}")),
+            Verdict::Signal(_) | Verdict::ExitCode(_) => {
+                SimulatorError::RuntimeError(format!("{self}\n{log}"))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::MemoryLimitExceeded => f.write_str("Memory Limit Exceeded"),
+            Verdict::TimeLimitExceeded => f.write_str("Time Limit Exceeded"),
+            Verdict::Signal(signal) => match Signal::try_from(*signal) {
+                Ok(Signal::SIGSEGV) => f.write_str("Segmentation Fault"),
+                Ok(name) => write!(f, "Killed by signal {signal} ({name})"),
+                Err(_) => write!(f, "Killed by signal {signal}"),
+            },
+            Verdict::ExitCode(code) => write!(f, "Exited with code {code}"),
+        }
+    }
+}
+
 pub struct Process {
     process: Child,
     process_type: ProcessType,
+    backend: Arc<dyn ContainerBackend>,
+    started: Instant,
+    usage: Option<ResourceUsage>,
+    exit: ContainerExit,
+    /// Whether the driver killed it, so that SIGKILL isn't held against it.
+    killed: bool,
+}
+
+impl std::fmt::Debug for Process {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Process")
+            .field("process", &self.process)
+            .field("process_type", &self.process_type)
+            .field("killed", &self.killed)
+            .finish()
+    }
 }
 
 impl Process {
-    pub fn new(proc: Child, proc_type: ProcessType) -> Self {
+    pub fn new(proc: Child, proc_type: ProcessType, backend: Arc<dyn ContainerBackend>) -> Self {
         Process {
             process: proc,
             process_type: proc_type,
+            backend,
+            started: Instant::now(),
+            usage: None,
+            exit: ContainerExit::default(),
+            killed: false,
         }
     }
 
@@ -47,14 +132,68 @@ impl Process {
         &self.process_type
     }
 
+    /// Reaps the process with `wait4`, recording what it and its descendants used and how its
+    /// container ended.
     pub fn wait(&mut self) -> Result<ExitStatus, SimulatorError> {
-        self.process.wait().map_err(|err| {
-            SimulatorError::UnidentifiedError(format!("Waiting on Child Failed: {err}"))
-        })
+        let pid = self.process.id() as libc::pid_t;
+        let mut status = 0;
+        let mut rusage = MaybeUninit::<libc::rusage>::zeroed();
+        while unsafe { libc::wait4(pid, &mut status, 0, rusage.as_mut_ptr()) } != pid {
+            let err = std::io::Error::last_os_error();
+            if err.kind() != ErrorKind::Interrupted {
+                return Err(SimulatorError::UnidentifiedError(format!(
+                    "Waiting on Child Failed: {err}"
+                )));
+            }
+        }
+        let wall_time_ms = self.started.elapsed().as_millis() as u64;
+        self.exit = self.backend.finish(pid as u32);
+        let rusage = unsafe { rusage.assume_init() };
+        let millis = |t: libc::timeval| t.tv_sec as u64 * 1000 + t.tv_usec as u64 / 1000;
+        let used = match self.backend.measures_usage() {
+            true => Some(ContainerUsage {
+                cpu_time_ms: millis(rusage.ru_utime) + millis(rusage.ru_stime),
+                peak_memory_kb: rusage.ru_maxrss as u64,
+            }),
+            false => self.exit.usage,
+        };
+        self.usage = used.map(|used| ResourceUsage {
+            wall_time_ms,
+            cpu_time_ms: used.cpu_time_ms,
+            peak_memory_kb: used.peak_memory_kb,
+        });
+        Ok(ExitStatus::from_raw(status))
+    }
+
+    /// What the process used, once it has been waited for.
+    pub fn usage(&self) -> Option<ResourceUsage> {
+        self.usage
+    }
+
+    /// How the process that exited with `status` is judged, `None` for a clean exit or the
+    /// driver's own SIGKILL.
+    pub fn verdict(&self, status: ExitStatus) -> Option<Verdict> {
+        if self.exit.oom_killed {
+            return Some(Verdict::MemoryLimitExceeded);
+        }
+        if self.killed && status.signal() == Some(libc::SIGKILL) {
+            return None;
+        }
+        Verdict::from_status(status)
     }
 
     pub fn kill(&mut self) {
-        let _ = self.process.kill();
+        self.killed = true;
+        self.backend.kill(&mut self.process);
+    }
+}
+
+/// Called with each complete line of a process's output as soon as it is read.
+pub struct LineWatcher(Box<dyn FnMut(&str)>);
+
+impl std::fmt::Debug for LineWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("LineWatcher")
     }
 }
 
@@ -63,17 +202,36 @@ pub struct ProcessOutput {
     stderr: ChildStderr,
     output: String,
     process_type: ProcessType,
+    max_log_size: usize,
+    on_line: Option<LineWatcher>,
+    /// How much of `output` was passed to `on_line` already.
+    watched: usize,
 }
 
 impl ProcessOutput {
-    pub fn new(stderr: ChildStderr, proc_type: ProcessType) -> Self {
+    pub fn new(stderr: ChildStderr, proc_type: ProcessType, max_log_size: usize) -> Self {
+        // Reads only drain what is already there, a live process must not block the event loop
+        if let Ok(flags) = fcntl(stderr.as_raw_fd(), FcntlArg::F_GETFL) {
+            let flags = OFlag::from_bits_truncate(flags) | OFlag::O_NONBLOCK;
+            let _ = fcntl(stderr.as_raw_fd(), FcntlArg::F_SETFL(flags));
+        }
         ProcessOutput {
             stderr,
             output: String::new(),
             process_type: proc_type,
+            max_log_size,
+            on_line: None,
+            watched: 0,
         }
     }
 
+    /// Calls `on_line` with every complete line as it is read, rather than only handing
+    /// out the whole output once the process is done.
+    pub fn on_line(mut self, on_line: impl FnMut(&str) + 'static) -> Self {
+        self.on_line = Some(LineWatcher(Box::new(on_line)));
+        self
+    }
+
     pub fn stderr(&self) -> &ChildStderr {
         &self.stderr
     }
@@ -87,19 +245,22 @@ impl ProcessOutput {
     }
 
     pub fn read_to_string(&mut self) -> Result<(), SimulatorError> {
-        let mut buf = String::new();
-        let map_err =
-            |err| SimulatorError::UnidentifiedError(format!("Error during log extraction: {err}"));
+        let mut bytes = vec![];
+        let drained = |res: std::io::Result<usize>| match res {
+            Err(err) if err.kind() != ErrorKind::WouldBlock => Err(
+                SimulatorError::UnidentifiedError(format!("Error during log extraction: {err}")),
+            ),
+            _ => Ok(()),
+        };
 
+        let mut buf;
         match self.process_type {
             ProcessType::Runner | ProcessType::RunnerPlayer1 | ProcessType::RunnerPlayer2 => {
-                let limit: usize = env::var("MAX_LOG_SIZE").unwrap().parse().unwrap();
+                let limit = self.max_log_size;
                 let stderr = &mut self.stderr;
 
-                let _ = stderr
-                    .take(limit as u64)
-                    .read_to_string(&mut buf)
-                    .map_err(map_err)?;
+                drained(stderr.take(limit as u64).read_to_end(&mut bytes))?;
+                buf = String::from_utf8_lossy(&bytes).into_owned();
                 if self.output.len() < limit {
                     let rem = limit - self.output.len();
                     buf = buf.chars().take(rem).collect::<String>();
@@ -108,11 +269,18 @@ impl ProcessOutput {
                 }
             }
             ProcessType::Simulator => {
-                let _ = self.stderr.read_to_string(&mut buf).map_err(map_err)?;
+                drained(self.stderr.read_to_end(&mut bytes))?;
+                buf = String::from_utf8_lossy(&bytes).into_owned();
             }
         }
 
         self.output.push_str(buf.as_str());
+        if let Some(LineWatcher(on_line)) = &mut self.on_line {
+            while let Some(end) = self.output[self.watched..].find('\n') {
+                on_line(&self.output[self.watched..self.watched + end]);
+                self.watched += end + 1;
+            }
+        }
         Ok(())
     }
 }
@@ -157,3 +325,29 @@ impl Pollable for EpollEntryType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+
+    use super::Verdict;
+
+    #[test]
+    fn exit_statuses_are_judged() {
+        let verdict = |raw| Verdict::from_status(ExitStatus::from_raw(raw));
+        assert_eq!(verdict(0), None);
+        assert_eq!(verdict(9), Some(Verdict::Signal(9)));
+        assert_eq!(verdict(137 << 8), Some(Verdict::Signal(9)));
+        assert_eq!(verdict(24), Some(Verdict::TimeLimitExceeded));
+        assert_eq!(verdict(1 << 8), Some(Verdict::ExitCode(1)));
+
+        let segfault = verdict(11).unwrap();
+        assert_eq!(segfault, verdict(139 << 8).unwrap());
+        assert_eq!(segfault.to_string(), "Segmentation Fault");
+        assert_eq!(
+            verdict(6).unwrap().to_string(),
+            "Killed by signal 6 (SIGABRT)"
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use std::{sync::Arc, thread, time::Duration};
+
+use log::{info, warn};
+
+use crate::{config::DriverConfig, container::ContainerBackend, game_dir::GameDir};
+
+/// Cleans up after games that are no longer running: containers left by a crashed driver and
+/// game directories that were never removed.
+#[derive(Clone)]
+pub struct Reaper {
+    config: Arc<DriverConfig>,
+    backend: Arc<dyn ContainerBackend>,
+}
+
+impl Reaper {
+    pub fn new(config: Arc<DriverConfig>, backend: Arc<dyn ContainerBackend>) -> Self {
+        Reaper { config, backend }
+    }
+
+    pub fn reap(&self) {
+        match self
+            .backend
+            .reap(&self.config.instance_id, &GameDir::is_live)
+        {
+            Ok(0) => {}
+            Ok(n) => info!("Removed {n} orphaned containers"),
+            Err(e) => warn!("Unable to remove orphaned containers: {e:?}"),
+        }
+        match GameDir::remove_stale(&self.config.game_dir_root, &self.config.instance_id) {
+            Ok(0) => {}
+            Ok(n) => info!("Removed {n} stale game directories"),
+            Err(e) => warn!("Unable to remove stale game directories: {e}"),
+        }
+    }
+
+    /// Removes the containers of every game, running or not, for when the driver is about to
+    /// exit without waiting for them. Their directories are swept at the next startup.
+    pub fn kill_all(&self) {
+        match self.backend.reap(&self.config.instance_id, &|_| false) {
+            Ok(0) => {}
+            Ok(n) => warn!("Killed {n} containers of unfinished games"),
+            Err(e) => warn!("Unable to kill the containers of unfinished games: {e:?}"),
+        }
+    }
+
+    /// Sweeps once right away, then every `reaper_interval` seconds on a background thread.
+    pub fn start(&self) {
+        self.reap();
+        if self.config.reaper_interval == 0 {
+            return;
+        }
+        let reaper = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(reaper.config.reaper_interval));
+            reaper.reap();
+        });
+    }
+}
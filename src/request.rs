@@ -1,4 +1,4 @@
-use std::os::fd::RawFd;
+use std::{collections::BTreeMap, os::fd::RawFd};
 
 use serde::de;
 use serde::Deserialize;
@@ -45,15 +45,30 @@ pub struct PvPGameParameters {
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
+#[serde(from = "String")]
 pub enum Language {
     CPP,
     JAVA,
     PYTHON,
+    /// One of the `languages` registered in the driver's config.
+    Other(String),
+}
+
+impl From<String> for Language {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "CPP" => Language::CPP,
+            "JAVA" => Language::JAVA,
+            "PYTHON" => Language::PYTHON,
+            _ => Language::Other(name),
+        }
+    }
 }
 
 pub enum GameRequest {
     NormalGame(NormalGameRequest),
     PvPGame(PvPGameRequest),
+    Validation(ValidationRequest),
 }
 
 impl From<NormalGameRequest> for GameRequest {
@@ -68,11 +83,18 @@ impl From<PvPGameRequest> for GameRequest {
     }
 }
 
+impl From<ValidationRequest> for GameRequest {
+    fn from(request: ValidationRequest) -> Self {
+        GameRequest::Validation(request)
+    }
+}
+
 impl GameRequest {
     pub fn game_id(&self) -> &String {
         match self {
             GameRequest::NormalGame(req) => &req.game_id,
             GameRequest::PvPGame(req) => &req.game_id,
+            GameRequest::Validation(req) => &req.game_id,
         }
     }
 }
@@ -97,6 +119,12 @@ pub struct NormalGameRequest {
 pub struct PlayerCode {
     pub source_code: String,
     pub language: Language,
+    /// Helper modules next to the source code, keyed by their path in the player's directory.
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    /// More helper modules, as a base64 tar, gzipped tar or zip archive.
+    #[serde(default)]
+    pub archive: Option<String>,
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
@@ -107,6 +135,13 @@ pub struct PvPGameRequest {
     pub player2: PlayerCode,
 }
 
+/// Only compiles the player's code, to tell about compilation errors before playing a game.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ValidationRequest {
+    pub game_id: String,
+    pub player_code: PlayerCode,
+}
+
 // Reference: https://serde.rs/attr-bound.html
 fn deserialize_from_str<'de, D>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error>
 where
@@ -184,6 +219,8 @@ mod tests {
             player_code: PlayerCode {
                 language: super::Language::PYTHON,
                 source_code: r#"print(x)"#.to_owned(),
+                files: Default::default(),
+                archive: None,
             },
         };
         let deserealized_example_request: NormalGameRequest =
@@ -246,10 +283,14 @@ mod tests {
             player1: PlayerCode {
                 language: super::Language::PYTHON,
                 source_code: r#"print(x)"#.to_owned(),
+                files: Default::default(),
+                archive: None,
             },
             player2: PlayerCode {
                 language: super::Language::PYTHON,
                 source_code: r#"print(x)"#.to_owned(),
+                files: Default::default(),
+                archive: None,
             },
         };
         let deserealized_example_request: PvPGameRequest =
@@ -1,5 +1,7 @@
 use serde::Serialize;
 
+use crate::error::SimulatorError;
+
 #[derive(Serialize, Debug, PartialEq)]
 #[allow(non_camel_case_types)]
 pub enum GameStatusEnum {
@@ -9,12 +11,119 @@ pub enum GameStatusEnum {
     EXECUTE_ERROR,
 }
 
+/// The finer grained stage of an `IDLE` or `EXECUTING` game. Consumers that only know
+/// `game_status` can ignore it.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum GameStage {
+    QUEUED,
+    COMPILING,
+    COMPILED,
+    RUNNING,
+}
+
+impl GameStage {
+    pub fn game_status(self) -> GameStatusEnum {
+        match self {
+            GameStage::QUEUED => GameStatusEnum::IDLE,
+            _ => GameStatusEnum::EXECUTING,
+        }
+    }
+}
+
+/// How far a normal game got, published to the progress queue while it is running.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GameProgress {
+    pub game_id: String,
+    pub turn: usize,
+    pub coins_left: u32,
+    pub destruction_percentage: f64,
+}
+
+impl GameProgress {
+    pub fn new(game_id: String, no_of_coins: u32) -> Self {
+        GameProgress {
+            game_id,
+            turn: 0,
+            coins_left: no_of_coins,
+            destruction_percentage: 0.0,
+        }
+    }
+
+    /// Takes in a line of the simulator's log, returns the progress up to the end of the
+    /// previous turn when it starts a new one.
+    pub fn update(&mut self, ln: &str) -> Option<GameProgress> {
+        let ln = ln.trim();
+        if let Some(turn) = ln.strip_prefix("TURN, ") {
+            let completed = Some(self.clone()).filter(|progress| progress.turn > 0);
+            self.turn = turn.parse().unwrap_or(self.turn);
+            return completed;
+        }
+        if let Some(x) = ln
+            .strip_prefix("DESTRUCTION, ")
+            .and_then(|s| s.strip_suffix('%'))
+            .and_then(|x| x.parse::<f64>().ok())
+        {
+            self.destruction_percentage = x;
+        }
+        if let Some(x) = ln
+            .strip_prefix("COINS, ")
+            .and_then(|x| x.parse::<u32>().ok())
+        {
+            self.coins_left = x;
+        }
+        None
+    }
+}
+
+/// Stable kinds of errors for clients to act on, rather than parsing the log.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum ErrorCode {
+    COMPILATION_ERROR,
+    RUNTIME_ERROR,
+    TIME_LIMIT,
+    MEMORY_LIMIT,
+    /// Something went wrong on the driver's side rather than with the player's code.
+    INTERNAL,
+}
+
+/// Why a game failed. The same is in the log as `ERRORS, ...` lines for older clients.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GameError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub details: String,
+}
+
+/// What one process used, as measured when it was reaped.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ResourceUsage {
+    pub wall_time_ms: u64,
+    pub cpu_time_ms: u64,
+    pub peak_memory_kb: u64,
+}
+
+/// How close a player got to the limits. Usage is missing for processes that never started,
+/// and when the backend couldn't measure it.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Resources {
+    /// Compiling, or checking the syntax of Python code.
+    pub compile_time_ms: u64,
+    pub player: Option<ResourceUsage>,
+    pub simulator: Option<ResourceUsage>,
+}
+
 #[derive(Serialize, Debug, PartialEq)]
 pub struct GameResult {
     pub destruction_percentage: f64,
     pub coins_used: u64,
     pub has_errors: bool,
     pub log: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<GameError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<Resources>,
 }
 
 #[derive(Serialize, Debug, PartialEq)]
@@ -22,6 +131,61 @@ pub struct GameResultPvP {
     pub score: u64,
     pub has_errors: bool,
     pub log: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<GameError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resources: Option<Resources>,
+}
+
+/// One error or warning the compiler pointed at the player's code.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Picks the `file:line[:column]: message` lines out of what gcc or javac printed, with
+    /// the files relative to the player's code.
+    pub fn parse(output: &str) -> Vec<Diagnostic> {
+        output
+            .lines()
+            .filter_map(|ln| {
+                let mut parts = ln.splitn(4, ':');
+                let file = parts.next()?.trim();
+                let line = parts.next()?.parse().ok()?;
+                let rest = parts.collect::<Vec<_>>().join(":");
+                let (column, message) = match rest.split_once(':') {
+                    Some((column, message)) => match column.parse() {
+                        Ok(column) => (Some(column), message),
+                        Err(_) => (None, rest.as_str()),
+                    },
+                    None => (None, rest.as_str()),
+                };
+                let file = file.strip_prefix("/player_code/").unwrap_or(file);
+                (!file.is_empty() && !file.contains(' ')).then(|| Diagnostic {
+                    file: file.to_owned(),
+                    line,
+                    column,
+                    message: message.trim().to_owned(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The outcome of a validation request.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CompilationResult {
+    pub success: bool,
+    pub diagnostics: Vec<Diagnostic>,
+    pub compile_time_ms: u64,
+    /// The compiler's output as is, or why the code couldn't be compiled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<GameError>,
 }
 
 #[derive(Serialize, Debug, PartialEq)]
@@ -31,6 +195,16 @@ pub struct GameStatus {
     pub game_result: Option<GameResult>,
     pub game_result_player1: Option<GameResultPvP>,
     pub game_result_player2: Option<GameResultPvP>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compilation_result: Option<CompilationResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<GameStage>,
+    /// When `stage` was reached, in milliseconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    /// The error behind an `EXECUTE_ERROR`, for the driver's own retry decisions.
+    #[serde(skip)]
+    pub failure: Option<SimulatorError>,
 }
 
 impl GameStatus {
@@ -45,6 +219,10 @@ impl GameStatus {
             game_result,
             game_result_player1: None,
             game_result_player2: None,
+            compilation_result: None,
+            stage: None,
+            timestamp: None,
+            failure: None,
         }
     }
 
@@ -60,14 +238,48 @@ impl GameStatus {
             game_result: None,
             game_result_player1,
             game_result_player2,
+            compilation_result: None,
+            stage: None,
+            timestamp: None,
+            failure: None,
+        }
+    }
+
+    pub fn new_validation(
+        game_id: String,
+        game_status: GameStatusEnum,
+        compilation_result: Option<CompilationResult>,
+    ) -> Self {
+        GameStatus {
+            compilation_result,
+            ..GameStatus::new_normal(game_id, game_status, None)
         }
     }
+
+    /// Attaches what the processes of a normal game used to its result.
+    pub fn with_resources(mut self, resources: Resources) -> Self {
+        if let Some(result) = &mut self.game_result {
+            result.resources = Some(resources);
+        }
+        self
+    }
+
+    /// Attaches what the processes of a pvp game used to each player's result.
+    pub fn with_pvp_resources(mut self, player1: Resources, player2: Resources) -> Self {
+        if let Some(result) = &mut self.game_result_player1 {
+            result.resources = Some(player1);
+        }
+        if let Some(result) = &mut self.game_result_player2 {
+            result.resources = Some(player2);
+        }
+        self
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::{GameStatus, GameStatusEnum};
+    use super::{Diagnostic, GameProgress, GameStatus, GameStatusEnum};
     #[test]
     pub fn serialization_test() {
         // An example respone
@@ -83,4 +295,43 @@ mod tests {
 
         assert_eq!(serialized_game_status, expected_response);
     }
+
+    #[test]
+    fn progress_follows_the_simulator_log() {
+        let mut progress = GameProgress::new("game".to_owned(), 100);
+        assert_eq!(progress.update("TURN, 1"), None);
+        assert_eq!(progress.update("COINS, 99"), None);
+        assert_eq!(progress.update("DESTRUCTION, 33.33%"), None);
+        let completed = progress.update("TURN, 2").unwrap();
+        assert_eq!(completed.turn, 1);
+        assert_eq!(completed.coins_left, 99);
+        assert_eq!(completed.destruction_percentage, 33.33);
+        assert_eq!(progress.turn, 2);
+    }
+
+    #[test]
+    fn diagnostics_are_picked_out_of_compiler_output() {
+        let gcc = "/player_code/run.cpp: In function 'int main()':\n\
+                   /player_code/run.cpp:4:5: error: 'x' was not declared in this scope\n\
+                   In file included from run.cpp:1:\n";
+        let javac = "Run.java:12: error: ';' expected\n1 error\n";
+        assert_eq!(
+            Diagnostic::parse(gcc),
+            vec![Diagnostic {
+                file: "run.cpp".to_owned(),
+                line: 4,
+                column: Some(5),
+                message: "error: 'x' was not declared in this scope".to_owned(),
+            }]
+        );
+        assert_eq!(
+            Diagnostic::parse(javac),
+            vec![Diagnostic {
+                file: "Run.java".to_owned(),
+                line: 12,
+                column: None,
+                message: "error: ';' expected".to_owned(),
+            }]
+        );
+    }
 }
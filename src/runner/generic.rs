@@ -0,0 +1,141 @@
+use std::{fs::File, process::Child, sync::Arc};
+
+use crate::{
+    bus::Report,
+    config::{DriverConfig, LanguageCommand, LanguageSpec},
+    container::{ContainerBackend, ContainerSpec, ResourceLimits},
+    error::SimulatorError,
+    request::Language,
+};
+
+use super::{compile, GameType, Runnable};
+
+/// Plays any of the `languages` registered in the config, as its entry there describes.
+pub struct Runner {
+    current_dir: String,
+    game_id: String,
+    player_dir: String,
+    language: String,
+    backend: Arc<dyn ContainerBackend>,
+}
+
+impl Runner {
+    pub fn new(
+        current_dir: String,
+        game_id: String,
+        player_dir: String,
+        language: String,
+        backend: Arc<dyn ContainerBackend>,
+    ) -> Self {
+        Runner {
+            current_dir,
+            game_id,
+            player_dir,
+            language,
+            backend,
+        }
+    }
+
+    fn spec<'a>(&self, config: &'a DriverConfig) -> Result<&'a LanguageSpec, SimulatorError> {
+        config.languages.get(&self.language).ok_or_else(|| {
+            SimulatorError::CompilationError(format!(
+                "{} is not a supported language",
+                self.language
+            ))
+        })
+    }
+
+    fn container(
+        &self,
+        config: &DriverConfig,
+        command: &LanguageCommand,
+        entry: &str,
+        game_type: &GameType,
+        role: &str,
+        limits: ResourceLimits,
+    ) -> ContainerSpec {
+        let role = format!("{}_{role}", self.language.to_lowercase());
+        let spec = ContainerSpec::new(
+            format!(
+                "{}_{}_{role}",
+                self.game_id,
+                self.player_dir.replace('/', "_")
+            ),
+            &command.image,
+            limits,
+        )
+        .owned_by(config, &self.game_id, &role)
+        .mount(
+            format!("{}/{}", self.current_dir, self.player_dir),
+            "/player_code",
+        );
+        command.command.iter().fold(spec, |spec, arg| {
+            spec.arg(
+                arg.replace("{entry}", entry)
+                    .replace("{game_type}", &game_type.to_string()),
+            )
+        })
+    }
+}
+
+impl Runnable for Runner {
+    fn compile(
+        &self,
+        config: &DriverConfig,
+        game_type: &GameType,
+        report: &Report,
+    ) -> Result<(), SimulatorError> {
+        let spec = self.spec(config)?;
+        let Some(command) = &spec.compile else {
+            return Ok(());
+        };
+        let compiler = self.container(
+            config,
+            command,
+            spec.entry(game_type),
+            game_type,
+            "compiler",
+            ResourceLimits::compilation(config, command.cpus),
+        );
+        let artifact = spec
+            .artifact
+            .as_ref()
+            .map(|artifact| format!("{}/{}/{artifact}", self.current_dir, self.player_dir));
+
+        compile(
+            config,
+            &self.backend,
+            compiler,
+            artifact.as_deref(),
+            &Language::Other(self.language.to_owned()),
+            game_type,
+            report,
+        )
+    }
+
+    fn run(
+        &self,
+        config: &DriverConfig,
+        stdin: File,
+        stdout: File,
+        game_type: GameType,
+        report: &Report,
+    ) -> Result<Child, SimulatorError> {
+        self.compile(config, &game_type, report)?;
+
+        let spec = self.spec(config)?;
+        let runner = self
+            .container(
+                config,
+                &spec.run,
+                spec.entry(&game_type),
+                &game_type,
+                "runner",
+                ResourceLimits::runtime(config),
+            )
+            .stdin(stdin)
+            .stdout(stdout);
+
+        self.backend.spawn(runner)
+    }
+}
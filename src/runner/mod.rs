@@ -1,8 +1,22 @@
-use std::{fs::File, process::Child};
+use std::{
+    fs::{self, File},
+    path::Path,
+    process::Child,
+    sync::Arc,
+};
 
-use crate::{error::SimulatorError, request::Language};
+use crate::{
+    bus::Report,
+    cache::ArtifactCache,
+    config::DriverConfig,
+    container::{ContainerBackend, ContainerSpec},
+    error::SimulatorError,
+    request::Language,
+    response::GameStage,
+};
 
 pub mod cpp;
+pub mod generic;
 pub mod java;
 pub mod py;
 pub mod simulator;
@@ -16,26 +30,112 @@ impl GameType {
     pub fn file_name(&self, language: Language) -> &str {
         match self {
             GameType::PvPGame => match language {
-                Language::CPP | Language::PYTHON => "runpvp",
+                Language::CPP | Language::PYTHON | Language::Other(_) => "runpvp",
                 Language::JAVA => "RunPvP",
             },
             GameType::NormalGame => match language {
-                Language::CPP | Language::PYTHON => "run",
+                Language::CPP | Language::PYTHON | Language::Other(_) => "run",
                 Language::JAVA => "Run",
             },
         }
     }
 }
 
-impl ToString for GameType {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for GameType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            GameType::NormalGame => "normal".to_owned(),
-            GameType::PvPGame => "pvp".to_owned(),
+            GameType::NormalGame => write!(f, "normal"),
+            GameType::PvPGame => write!(f, "pvp"),
         }
     }
 }
 
+/// Compiles the code mounted into `compiler` into `artifact`, unless the artifact of the same
+/// code, language, game type and compiler, down to its entrypoint and args, is in the artifact
+/// cache. Compilers without an
+/// artifact, which only check the code, are never cached.
+fn compile(
+    config: &DriverConfig,
+    backend: &Arc<dyn ContainerBackend>,
+    compiler: ContainerSpec,
+    artifact: Option<&str>,
+    language: &Language,
+    game_type: &GameType,
+    report: &Report,
+) -> Result<(), SimulatorError> {
+    let artifact = artifact.map(Path::new);
+    if let Some(artifact) = artifact {
+        // Left behind by an earlier attempt at the game, and not part of the code
+        let _ = fs::remove_file(artifact);
+    }
+    let cache = artifact.and_then(|_| {
+        let cache = ArtifactCache::new(config)?;
+        let code_dir = Path::new(&compiler.mounts.first()?.host_path);
+        let entrypoint = backend.entrypoint(&compiler.image);
+        let mut parts = vec![
+            format!("{language:?}"),
+            game_type.to_string(),
+            compiler.image.to_owned(),
+            backend.image_digest(&compiler.image),
+            // Keeps the entrypoint and the args apart
+            entrypoint.len().to_string(),
+        ];
+        parts.extend(entrypoint);
+        parts.extend(compiler.args.iter().cloned());
+        let parts: Vec<_> = parts.iter().map(String::as_str).collect();
+        match ArtifactCache::key(code_dir, &parts) {
+            Ok(key) => Some((cache, key)),
+            Err(e) => {
+                log::warn!(
+                    "Unable to hash {}, compiling it uncached: {e}",
+                    code_dir.display()
+                );
+                None
+            }
+        }
+    });
+    if let (Some((cache, key)), Some(artifact)) = (&cache, artifact) {
+        if cache.restore(key, artifact) {
+            log::info!("Reusing the cached build {key} for {}", compiler.name);
+            report.stage(GameStage::COMPILED);
+            return Ok(());
+        }
+    }
+
+    report.stage(GameStage::COMPILING);
+    let out = backend.run(compiler)?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr).into_owned();
+        return Err(SimulatorError::CompilationError(stderr));
+    }
+    report.stage(GameStage::COMPILED);
+
+    if let (Some((cache, key)), Some(artifact)) = (cache, artifact) {
+        if let Err(e) = cache.store(&key, artifact) {
+            log::warn!("Unable to cache the build {key}: {e}");
+        }
+    }
+    Ok(())
+}
+
 pub trait Runnable {
-    fn run(&self, stdin: File, stdout: File, game_type: GameType) -> Result<Child, SimulatorError>;
+    /// Compiles the player's code, for the languages that need it.
+    fn compile(
+        &self,
+        _config: &DriverConfig,
+        _game_type: &GameType,
+        _report: &Report,
+    ) -> Result<(), SimulatorError> {
+        Ok(())
+    }
+
+    /// Compiles the player's code if needed and spawns it, calling `report` as it goes.
+    fn run(
+        &self,
+        config: &DriverConfig,
+        stdin: File,
+        stdout: File,
+        game_type: GameType,
+        report: &Report,
+    ) -> Result<Child, SimulatorError>;
 }
@@ -1,77 +1,121 @@
-use std::{
-    env,
-    fs::File,
-    os::linux::process::CommandExt,
-    process::{Command, Stdio},
+use std::{fs::File, process::Child, sync::Arc};
+
+use crate::{
+    bus::Report,
+    config::DriverConfig,
+    container::{ContainerBackend, ContainerSpec, ResourceLimits},
+    error::SimulatorError,
+    request::Language,
 };
 
-use crate::error::SimulatorError;
+use super::{compile, GameType, Runnable};
 
-use super::{GameType, Runnable};
+/// Compiles every file of the player's code without running it, printing a syntax error the
+/// way gcc prints its errors.
+const SYNTAX_CHECK: &str = r#"
+import os, sys
+paths = sorted(
+    os.path.relpath(os.path.join(dir, name))
+    for dir, _, names in os.walk(".")
+    for name in names
+    if name.endswith(".py")
+)
+for path in paths:
+    try:
+        with open(path) as source:
+            compile(source.read(), path, "exec")
+    except SyntaxError as e:
+        print(f"{path}:{e.lineno or 1}:{e.offset or 1}: {type(e).__name__}: {e.msg}", file=sys.stderr)
+        sys.exit(1)
+"#;
 
 pub struct Runner {
     current_dir: String,
     game_id: String,
     player_dir: String,
+    backend: Arc<dyn ContainerBackend>,
 }
 
 impl Runner {
-    pub fn new(current_dir: String, game_id: String, player_dir: String) -> Self {
+    pub fn new(
+        current_dir: String,
+        game_id: String,
+        player_dir: String,
+        backend: Arc<dyn ContainerBackend>,
+    ) -> Self {
         Runner {
             current_dir,
             game_id,
             player_dir,
+            backend,
         }
     }
 }
 
 impl Runnable for Runner {
+    fn compile(
+        &self,
+        config: &DriverConfig,
+        game_type: &GameType,
+        report: &Report,
+    ) -> Result<(), SimulatorError> {
+        let check = ContainerSpec::new(
+            format!(
+                "{}_{}_python_compiler",
+                self.game_id,
+                self.player_dir.replace('/', "_")
+            ),
+            &config.python_runner_image,
+            ResourceLimits::compilation(config, 1.0),
+        )
+        .owned_by(config, &self.game_id, "python_compiler")
+        .mount(
+            format!("{}/{}", self.current_dir, self.player_dir),
+            "/player_code",
+        )
+        .arg("-c")
+        .arg(SYNTAX_CHECK);
+
+        compile(
+            config,
+            &self.backend,
+            check,
+            None,
+            &Language::PYTHON,
+            game_type,
+            report,
+        )
+    }
+
     fn run(
         &self,
+        config: &DriverConfig,
         stdin: File,
         stdout: File,
         game_type: GameType,
-    ) -> Result<std::process::Child, SimulatorError> {
-        Command::new("docker")
-            .args([
-                "run",
-                &format!("--memory={}", env::var("RUNTIME_MEMORY_LIMIT").unwrap()),
-                &format!(
-                    "--memory-swap={}",
-                    env::var("RUNTIME_MEMORY_LIMIT").unwrap()
-                ),
-                "--cpus=1",
-                "--ulimit",
-                &format!(
-                    "cpu={}:{}",
-                    env::var("RUNTIME_TIME_LIMIT").unwrap(),
-                    env::var("RUNTIME_TIME_LIMIT").unwrap()
-                ),
-                "--rm",
-                "--name",
-                &format!(
-                    "{}_{}_python_runner",
-                    self.game_id,
-                    self.player_dir.replace('/', "_")
-                ),
-                "-i",
-                "-v",
-                format!("{}/{}:/player_code", self.current_dir, self.player_dir).as_str(),
-                &env::var("PYTHON_RUNNER_IMAGE").unwrap(),
-                "-u",
-                "main.py", //filename to start execution
-                &game_type.to_string(),
-            ])
-            .create_pidfd(true)
-            .current_dir(&self.current_dir)
-            .stdin(stdin)
-            .stdout(stdout)
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|err| {
-                SimulatorError::UnidentifiedError(format!(
-                    "Couldnt spawn the python runner process: {err}"
-                ))
-            })
+        report: &Report,
+    ) -> Result<Child, SimulatorError> {
+        self.compile(config, &game_type, report)?;
+        let runner = ContainerSpec::new(
+            format!(
+                "{}_{}_python_runner",
+                self.game_id,
+                self.player_dir.replace('/', "_")
+            ),
+            &config.python_runner_image,
+            ResourceLimits::runtime(config),
+        )
+        .owned_by(config, &self.game_id, "python_runner")
+        .mount(
+            format!("{}/{}", self.current_dir, self.player_dir),
+            "/player_code",
+        )
+        .arg("-u")
+        .arg("main.py") //filename to start execution
+        .arg(game_type.to_string())
+        .stdin(stdin)
+        .stdout(stdout);
+
+        self.backend.spawn(runner)
     }
 }
@@ -1,23 +1,31 @@
 use std::fs::File;
+use std::sync::Arc;
 
-use std::os::linux::process::CommandExt;
-use std::process::{Command, Stdio};
-
-use std::env;
-
-use crate::error::SimulatorError;
+use crate::{
+    config::DriverConfig,
+    container::{ContainerBackend, ContainerSpec, ResourceLimits},
+    error::SimulatorError,
+};
 
 pub struct Simulator {
     game_id: String,
+    game_dir: String,
+    backend: Arc<dyn ContainerBackend>,
 }
 
 impl Simulator {
-    pub fn new(game_id: String) -> Self {
-        Simulator { game_id }
+    pub fn new(game_id: String, game_dir: String, backend: Arc<dyn ContainerBackend>) -> Self {
+        Simulator {
+            game_id,
+            game_dir,
+            backend,
+        }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn run_pvp(
         &self,
+        config: &DriverConfig,
         stdin: File,
         stdout: File,
         p1_r: String,
@@ -25,78 +33,40 @@ impl Simulator {
         p2_r: String,
         p2_w: String,
     ) -> Result<std::process::Child, SimulatorError> {
-        Command::new("docker")
-            .args([
-                "run",
-                &format!("--memory={}", env::var("RUNTIME_MEMORY_LIMIT").unwrap()),
-                &format!(
-                    "--memory-swap={}",
-                    env::var("RUNTIME_MEMORY_LIMIT").unwrap()
-                ),
-                "--cpus=1",
-                "--ulimit",
-                &format!(
-                    "cpu={}:{}",
-                    env::var("RUNTIME_TIME_LIMIT").unwrap(),
-                    env::var("RUNTIME_TIME_LIMIT").unwrap()
-                ),
-                "--name",
-                &format!("{}_simulator", self.game_id),
-                "--rm",
-                "-i",
-                "-v",
-                &format!("/tmp/{}:/tmp/{}", self.game_id, self.game_id),
-                &env::var("SIMULATOR_IMAGE").unwrap(),
-                "--type=PvP",
-                &format!("p1_in={p1_r}"),  //p1_in
-                &format!("p1_out={p1_w}"), // p3_in
-                &format!("p2_in={p2_r}"),  // p2_in
-                &format!("p2_out={p2_w}"), // p4_in
-            ])
-            .create_pidfd(true)
-            .stdin(stdin)
-            .stdout(stdout)
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|err| {
-                SimulatorError::UnidentifiedError(format!(
-                    "Couldnt spawn the simulator process: {err}"
-                ))
-            })
+        let simulator = ContainerSpec::new(
+            format!("{}_simulator", self.game_id),
+            &config.simulator_image,
+            ResourceLimits::runtime(config),
+        )
+        .owned_by(config, &self.game_id, "simulator")
+        .mount(&self.game_dir, &self.game_dir)
+        .arg("--type=PvP")
+        .arg(format!("p1_in={p1_r}")) //p1_in
+        .arg(format!("p1_out={p1_w}")) // p3_in
+        .arg(format!("p2_in={p2_r}")) // p2_in
+        .arg(format!("p2_out={p2_w}")) // p4_in
+        .stdin(stdin)
+        .stdout(stdout);
+
+        self.backend.spawn(simulator)
     }
 
-    pub fn run(&self, stdin: File, stdout: File) -> Result<std::process::Child, SimulatorError> {
-        Command::new("docker")
-            .args([
-                "run",
-                &format!("--memory={}", env::var("RUNTIME_MEMORY_LIMIT").unwrap()),
-                &format!(
-                    "--memory-swap={}",
-                    env::var("RUNTIME_MEMORY_LIMIT").unwrap()
-                ),
-                "--cpus=1",
-                "--ulimit",
-                &format!(
-                    "cpu={}:{}",
-                    env::var("RUNTIME_TIME_LIMIT").unwrap(),
-                    env::var("RUNTIME_TIME_LIMIT").unwrap()
-                ),
-                "--rm",
-                "--name",
-                &format!("{}_simulator", self.game_id),
-                "-i",
-                &env::var("SIMULATOR_IMAGE").unwrap(),
-                "--type=Normal",
-            ])
-            .create_pidfd(true)
-            .stdin(stdin)
-            .stdout(stdout)
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|err| {
-                SimulatorError::UnidentifiedError(format!(
-                    "Couldnt spawn the simulator process: {err}"
-                ))
-            })
+    pub fn run(
+        &self,
+        config: &DriverConfig,
+        stdin: File,
+        stdout: File,
+    ) -> Result<std::process::Child, SimulatorError> {
+        let simulator = ContainerSpec::new(
+            format!("{}_simulator", self.game_id),
+            &config.simulator_image,
+            ResourceLimits::runtime(config),
+        )
+        .owned_by(config, &self.game_id, "simulator")
+        .arg("--type=Normal")
+        .stdin(stdin)
+        .stdout(stdout);
+
+        self.backend.spawn(simulator)
     }
 }
@@ -0,0 +1,26 @@
+use crossbeam_channel::Receiver;
+use nix::sys::{
+    signal::{SigSet, Signal},
+    signalfd::SignalFd,
+};
+
+/// Blocks SIGTERM and SIGINT for the calling thread and every thread it spawns afterwards,
+/// and returns a receiver that is disconnected once either arrives. Call it before spawning
+/// any thread, or the signal may be delivered to a thread that doesn't block it.
+pub fn on_signal() -> nix::Result<Receiver<()>> {
+    let mut mask = SigSet::empty();
+    mask.add(Signal::SIGTERM);
+    mask.add(Signal::SIGINT);
+    mask.thread_block()?;
+    let mut fd = SignalFd::new(&mask)?;
+
+    let (s, r) = crossbeam_channel::bounded::<()>(0);
+    std::thread::spawn(move || {
+        match fd.read_signal() {
+            Ok(Some(info)) => log::info!("Received signal {}, shutting down", info.ssi_signo),
+            res => log::error!("Unable to wait for signals, shutting down: {res:?}"),
+        }
+        drop(s);
+    });
+    Ok(r)
+}
@@ -0,0 +1,262 @@
+use std::{
+    fs::{self, File},
+    io::{self, Cursor, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use base64::Engine;
+
+use crate::{config::DriverConfig, error::SimulatorError, request::PlayerCode};
+
+/// How much code a player may submit, the source code included.
+pub struct SubmissionLimits {
+    pub max_files: usize,
+    pub max_bytes: u64,
+}
+
+impl SubmissionLimits {
+    pub fn new(config: &DriverConfig) -> Self {
+        SubmissionLimits {
+            max_files: config.max_player_files,
+            max_bytes: config.max_player_code_size << 10,
+        }
+    }
+}
+
+/// The files of a submission besides its source code, checked against the limits as they are
+/// read so an archive can't be inflated past them.
+struct Files<'a> {
+    limits: &'a SubmissionLimits,
+    files: Vec<(PathBuf, Vec<u8>)>,
+    bytes: u64,
+}
+
+impl Files<'_> {
+    fn add(&mut self, name: &str, contents: impl Read) -> Result<(), SimulatorError> {
+        let path = relative_path(name)?;
+        // The source code is one of them
+        if self.files.len() + 2 > self.limits.max_files {
+            return Err(rejected(format!(
+                "More than {} files were submitted",
+                self.limits.max_files
+            )));
+        }
+        let mut data = vec![];
+        contents
+            .take(self.limits.max_bytes.saturating_sub(self.bytes) + 1)
+            .read_to_end(&mut data)
+            .map_err(|e| rejected(format!("Unable to read {name} from the archive: {e}")))?;
+        self.bytes += data.len() as u64;
+        if self.bytes > self.limits.max_bytes {
+            return Err(rejected(format!(
+                "The submitted code is larger than {} KB",
+                self.limits.max_bytes >> 10
+            )));
+        }
+        self.files.push((path, data));
+        Ok(())
+    }
+
+    fn add_tar(&mut self, archive: impl Read) -> Result<(), SimulatorError> {
+        let unreadable = |e: io::Error| rejected(format!("Unable to read the archive: {e}"));
+        let mut archive = tar::Archive::new(archive);
+        for entry in archive.entries().map_err(unreadable)? {
+            let entry = entry.map_err(unreadable)?;
+            let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => {}
+                tar::EntryType::Regular => self.add(&name, entry)?,
+                _ => return Err(rejected(format!("{name} is not a regular file"))),
+            }
+        }
+        Ok(())
+    }
+
+    fn add_zip(&mut self, archive: &[u8]) -> Result<(), SimulatorError> {
+        let unreadable =
+            |e: zip::result::ZipError| rejected(format!("Unable to read the archive: {e}"));
+        let mut archive = zip::ZipArchive::new(Cursor::new(archive)).map_err(unreadable)?;
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).map_err(unreadable)?;
+            let name = file.name().to_owned();
+            // Symbolic links are stored as files with the link's target as their contents
+            let is_link = file
+                .unix_mode()
+                .is_some_and(|mode| mode & 0o170000 == 0o120000);
+            if is_link {
+                return Err(rejected(format!("{name} is not a regular file")));
+            }
+            if !file.is_dir() {
+                self.add(&name, file)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rejected(message: String) -> SimulatorError {
+    SimulatorError::CompilationError(message)
+}
+
+/// `name` as a path that can only lead into the player's directory.
+fn relative_path(name: &str) -> Result<PathBuf, SimulatorError> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => {
+                return Err(rejected(format!(
+                    "{name} is outside the player's directory"
+                )))
+            }
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err(rejected(format!("{name:?} is not a file name")));
+    }
+    Ok(path)
+}
+
+/// Writes the `files` and the extracted `archive` of `player_code` into `dir`, which already
+/// holds the boilerplate and the source code. Neither of those can be replaced.
+pub fn write_files(
+    dir: &Path,
+    player_code: &PlayerCode,
+    limits: &SubmissionLimits,
+) -> Result<(), SimulatorError> {
+    let mut files = Files {
+        limits,
+        files: vec![],
+        bytes: player_code.source_code.len() as u64,
+    };
+    for (name, contents) in &player_code.files {
+        files.add(name, contents.as_bytes())?;
+    }
+    if let Some(archive) = &player_code.archive {
+        let archive = base64::engine::general_purpose::STANDARD
+            .decode(archive.trim())
+            .map_err(|e| rejected(format!("The archive is not valid base64: {e}")))?;
+        match archive.as_slice() {
+            [b'P', b'K', 3, 4, ..] => files.add_zip(&archive)?,
+            [0x1f, 0x8b, ..] => files.add_tar(flate2::read::GzDecoder::new(archive.as_slice()))?,
+            _ => files.add_tar(archive.as_slice())?,
+        }
+    }
+
+    for (path, contents) in files.files {
+        let target = dir.join(&path);
+        if target.symlink_metadata().is_ok() {
+            return Err(rejected(format!(
+                "{} would replace a file of the boilerplate",
+                path.display()
+            )));
+        }
+        let written = target
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| File::create_new(&target))
+            .and_then(|mut file| file.write_all(&contents));
+        if let Err(e) = written {
+            return Err(rejected(format!("Unable to write {}: {e}", path.display())));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs};
+
+    use base64::Engine;
+
+    use super::{write_files, SubmissionLimits};
+    use crate::{
+        error::SimulatorError,
+        request::{Language, PlayerCode},
+    };
+
+    fn tar(files: &[(&str, &str)]) -> String {
+        let mut builder = tar::Builder::new(vec![]);
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            // Bypasses the checks `append_data` makes, to build hostile archives
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_cksum();
+            builder.append(&header, contents.as_bytes()).unwrap();
+        }
+        base64::engine::general_purpose::STANDARD.encode(builder.into_inner().unwrap())
+    }
+
+    fn zip(name: &str, contents: &str) -> String {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        let options = zip::write::FileOptions::default();
+        writer.start_file(name, options).unwrap();
+        std::io::Write::write_all(&mut writer, contents.as_bytes()).unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+        base64::engine::general_purpose::STANDARD.encode(archive)
+    }
+
+    #[test]
+    fn submissions_stay_inside_the_player_directory() {
+        let dir = std::env::temp_dir().join(format!("cc-driver-submission-{}", std::process::id()));
+        let limits = SubmissionLimits {
+            max_files: 4,
+            max_bytes: 100,
+        };
+        let submit = |files: &[(&str, &str)], archive: Option<String>| {
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("main.py"), "import run").unwrap();
+            let player_code = PlayerCode {
+                source_code: "import helpers.moves".to_owned(),
+                language: Language::PYTHON,
+                files: files
+                    .iter()
+                    .map(|(name, contents)| (name.to_string(), contents.to_string()))
+                    .collect::<BTreeMap<_, _>>(),
+                archive,
+            };
+            write_files(&dir, &player_code, &limits)
+        };
+
+        submit(
+            &[("helpers/moves.py", "x = 1")],
+            Some(tar(&[("./util.py", "y = 2")])),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("helpers/moves.py")).unwrap(),
+            "x = 1"
+        );
+        assert_eq!(fs::read_to_string(dir.join("util.py")).unwrap(), "y = 2");
+
+        let rejected = |result: Result<(), SimulatorError>| {
+            matches!(result, Err(SimulatorError::CompilationError(_)))
+        };
+        submit(&[], Some(zip("lib/util.py", "z = 3"))).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("lib/util.py")).unwrap(),
+            "z = 3"
+        );
+
+        assert!(rejected(submit(&[("../escape.py", "")], None)));
+        assert!(rejected(submit(&[], Some(zip("../escape.py", "")))));
+        assert!(rejected(submit(&[("/etc/escape.py", "")], None)));
+        assert!(rejected(submit(
+            &[],
+            Some(tar(&[("a/../../escape.py", "")]))
+        )));
+        assert!(rejected(submit(&[("main.py", "import os")], None)));
+        assert!(rejected(submit(
+            &[("a", ""), ("b", ""), ("c", ""), ("d", "")],
+            None
+        )));
+        assert!(rejected(submit(&[("big.py", &"#".repeat(100))], None)));
+        assert!(!dir.parent().unwrap().join("escape.py").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,4 @@
 use std::{
-    env,
     fs::File,
     io::{BufWriter, Write},
 };
@@ -7,12 +6,13 @@ use std::{
 use fs_extra::dir::CopyOptions;
 
 use crate::{
-    create_normal_error_response, error,
-    create_pvp_error_response,
+    config::DriverConfig,
+    create_normal_error_response, create_pvp_error_response, error,
     game_dir::GameDir,
     request::{Attacker, Defender, Language, NormalGameRequest, PlayerCode, PvPGameRequest},
     response::{self, GameStatus},
     runner::GameType,
+    submission::{self, SubmissionLimits},
 };
 
 pub fn copy_dir_all(
@@ -20,12 +20,12 @@ pub fn copy_dir_all(
     dst: impl AsRef<std::path::Path>,
 ) -> std::io::Result<()> {
     let opt = CopyOptions::new();
-    for entry in std::fs::read_dir(src).unwrap() {
+    for entry in std::fs::read_dir(src)? {
         let entry = entry?;
         let ty = entry.file_type()?;
         if ty.is_dir() {
             fs_extra::dir::copy(entry.path(), dst.as_ref(), &opt)
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", e)))?;
+                .map_err(|e| std::io::Error::other(format!("{:?}", e)))?;
         } else {
             std::fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
         }
@@ -104,7 +104,11 @@ pub fn send_initial_pvp_input(fifos: Vec<&File>, pvp_request: &PvPGameRequest) {
     }
 }
 
-pub fn send_initial_input(fifos: Vec<&File>, normal_game_request: &NormalGameRequest) {
+pub fn send_initial_input(
+    fifos: Vec<&File>,
+    normal_game_request: &NormalGameRequest,
+    map_size: usize,
+) {
     for fifo in fifos {
         let mut writer = BufWriter::new(fifo);
         writer
@@ -123,14 +127,7 @@ pub fn send_initial_input(fifos: Vec<&File>, normal_game_request: &NormalGameReq
             &normal_game_request.parameters.defenders,
         );
         writer
-            .write_all(
-                format!(
-                    "{} {}\n",
-                    env::var("MAP_SIZE").unwrap(),
-                    env::var("MAP_SIZE").unwrap()
-                )
-                .as_bytes(),
-            )
+            .write_all(format!("{map_size} {map_size}\n").as_bytes())
             .unwrap();
 
         for row in normal_game_request.map.iter() {
@@ -146,69 +143,79 @@ pub fn make_copy(
     src_dir: &str,
     dest_dir: &str,
     player_code_file: &str,
-    game_id: &String,
+    game_id: &str,
     player_code: &PlayerCode,
     game_type: &GameType,
 ) -> Option<response::GameStatus> {
     if let Err(e) = copy_dir_all(src_dir, dest_dir) {
         match game_type {
-            GameType::NormalGame => {return Some(create_normal_error_response(
-                game_id.clone(),
-                error::SimulatorError::UnidentifiedError(format!(
-                    "Failed to copy player code boilerplate: {e}"
-                )),
-            ));}
-            GameType::PvPGame => {return Some(create_pvp_error_response(
-                game_id.clone(),
-                error::SimulatorError::UnidentifiedError(format!(
-                    "Failed to copy player code boilerplate: {e}"
-                )),
-                error::SimulatorError::UnidentifiedError(format!(
-                    "Failed to copy player code boilerplate: {e}"
-                )),
-                true,
-                true,
-            ));}
+            GameType::NormalGame => {
+                return Some(create_normal_error_response(
+                    game_id.to_owned(),
+                    error::SimulatorError::UnidentifiedError(format!(
+                        "Failed to copy player code boilerplate: {e}"
+                    )),
+                ));
+            }
+            GameType::PvPGame => {
+                return Some(create_pvp_error_response(
+                    game_id.to_owned(),
+                    error::SimulatorError::UnidentifiedError(format!(
+                        "Failed to copy player code boilerplate: {e}"
+                    )),
+                    error::SimulatorError::UnidentifiedError(format!(
+                        "Failed to copy player code boilerplate: {e}"
+                    )),
+                    true,
+                    true,
+                ));
+            }
         }
     }
 
     if let Err(e) = std::fs::File::create(player_code_file).and_then(|mut file| {
-                file.write_all(player_code.source_code.as_bytes())
+        file.write_all(player_code.source_code.as_bytes())
             .and_then(|_| file.sync_all())
     }) {
         match game_type {
-            GameType::NormalGame => {return Some(create_normal_error_response(
-                game_id.clone(),
-                error::SimulatorError::UnidentifiedError(format!(
-                    "Failed to copy player code : {e}"
-                )),
-            ));}
-            GameType::PvPGame => {return Some(create_pvp_error_response(
-                game_id.clone(),
-                error::SimulatorError::UnidentifiedError(format!(
-                    "Failed to copy player code : {e}"
-                )),
-                error::SimulatorError::UnidentifiedError(format!(
-                    "Failed to copy player code : {e}"
-                )),
-                true,
-                true,
-            ));}
+            GameType::NormalGame => {
+                return Some(create_normal_error_response(
+                    game_id.to_owned(),
+                    error::SimulatorError::UnidentifiedError(format!(
+                        "Failed to copy player code : {e}"
+                    )),
+                ));
+            }
+            GameType::PvPGame => {
+                return Some(create_pvp_error_response(
+                    game_id.to_owned(),
+                    error::SimulatorError::UnidentifiedError(format!(
+                        "Failed to copy player code : {e}"
+                    )),
+                    error::SimulatorError::UnidentifiedError(format!(
+                        "Failed to copy player code : {e}"
+                    )),
+                    true,
+                    true,
+                ));
+            }
         }
     }
     None
 }
 
 pub fn copy_files(
-    game_id: &String,
+    config: &DriverConfig,
+    game_id: &str,
     player_code: &PlayerCode,
     game_dir_handle: &GameDir,
-    player_dir: &String,
+    player_dir: &str,
     game_type: &GameType,
 ) -> Option<GameStatus> {
-    let (to_copy_dir, player_code_file) = match player_code.language {
+    let boilerplate_dir = &config.player_code_dir;
+    let (to_copy_dir, player_code_file) = match &player_code.language {
         Language::CPP => (
-            "player_code/cpp",
+            format!("{boilerplate_dir}/cpp"),
             format!(
                 "{}/{}/{}.cpp",
                 game_dir_handle.get_path(),
@@ -217,7 +224,7 @@ pub fn copy_files(
             ),
         ),
         Language::PYTHON => (
-            "player_code/python",
+            format!("{boilerplate_dir}/python"),
             format!(
                 "{}/{}/{}.py",
                 game_dir_handle.get_path(),
@@ -226,7 +233,7 @@ pub fn copy_files(
             ),
         ),
         Language::JAVA => (
-            "player_code/java",
+            format!("{boilerplate_dir}/java"),
             format!(
                 "{}/{}/{}.java",
                 game_dir_handle.get_path(),
@@ -234,14 +241,50 @@ pub fn copy_files(
                 game_type.file_name(Language::JAVA)
             ),
         ),
+        Language::Other(name) => match config.languages.get(name) {
+            Some(language) => (
+                format!("{boilerplate_dir}/{}", language.boilerplate),
+                format!(
+                    "{}/{}/{}",
+                    game_dir_handle.get_path(),
+                    player_dir,
+                    language.entry(game_type)
+                ),
+            ),
+            None => {
+                // The player's mistake, not worth retrying
+                let e = error::SimulatorError::CompilationError(format!(
+                    "{name} is not a supported language"
+                ));
+                return Some(match game_type {
+                    GameType::NormalGame => create_normal_error_response(game_id.to_owned(), e),
+                    GameType::PvPGame => {
+                        create_pvp_error_response(game_id.to_owned(), e.clone(), e, true, true)
+                    }
+                });
+            }
+        },
     };
 
-    make_copy(
-        to_copy_dir,
-        format!("{}/{}", game_dir_handle.get_path(), player_dir).as_str(),
+    let dest_dir = format!("{}/{}", game_dir_handle.get_path(), player_dir);
+    if let Some(resp) = make_copy(
+        &to_copy_dir,
+        &dest_dir,
         &player_code_file,
         game_id,
         player_code,
         game_type,
-    )
+    ) {
+        return Some(resp);
+    }
+
+    let limits = SubmissionLimits::new(config);
+    submission::write_files(std::path::Path::new(&dest_dir), player_code, &limits)
+        .err()
+        .map(|e| match game_type {
+            GameType::NormalGame => create_normal_error_response(game_id.to_owned(), e),
+            GameType::PvPGame => {
+                create_pvp_error_response(game_id.to_owned(), e.clone(), e, true, true)
+            }
+        })
 }
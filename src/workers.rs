@@ -0,0 +1,200 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::{
+    bus::{Job, Outcome, Report},
+    config::DriverConfig,
+    create_executing_response,
+    error::SimulatorError,
+    handlers::Handler,
+    request::GameRequest,
+};
+
+/// The jobs of a pool as its workers take them.
+pub struct Jobs {
+    receiver: Receiver<Job>,
+    finishing: Arc<AtomicBool>,
+}
+
+impl Jobs {
+    /// The next job to play, `None` once every sender is gone. Once the pools are finishing,
+    /// the jobs still queued are put back rather than started.
+    pub fn next(&self) -> Option<Job> {
+        loop {
+            let job = self.receiver.recv().ok()?;
+            if !self.finishing.load(Ordering::Acquire) {
+                return Some(job);
+            }
+            log::info!("Putting back game {}", job.request.game_id());
+        }
+    }
+}
+
+/// The pools of worker threads playing normal and PvP games, shared by every frontend.
+/// PvP games get workers of their own, so a flood of them can't hold up normal games.
+pub struct Workers {
+    normal: Sender<Job>,
+    pvp: Sender<Job>,
+    receivers: Vec<Receiver<Job>>,
+    /// Set first thing when finishing, so no worker starts another job.
+    finishing: Arc<AtomicBool>,
+    /// Disconnected once every worker has returned.
+    running: Receiver<()>,
+}
+
+impl Workers {
+    pub fn start(config: Arc<DriverConfig>, handler_fn: fn(Jobs, Arc<DriverConfig>)) -> Self {
+        let (running_s, running) = crossbeam_channel::bounded::<()>(0);
+        let finishing = Arc::new(AtomicBool::new(false));
+        let pool = |workers: usize, capacity: usize| {
+            let (s, r) = crossbeam_channel::bounded(capacity);
+            // each thread has a receiver
+            for _ in 0..workers {
+                let new_r = Jobs {
+                    receiver: r.clone(),
+                    finishing: Arc::clone(&finishing),
+                };
+                let config_clone = Arc::clone(&config);
+                let running = running_s.clone();
+                thread::spawn(move || {
+                    handler_fn(new_r, config_clone);
+                    drop(running);
+                });
+            }
+            (s, r)
+        };
+        let (normal, normal_r) = pool(
+            config.normal_pool.workers,
+            config.normal_pool.job_channel_capacity,
+        );
+        let (pvp, pvp_r) = pool(
+            config.pvp_pool.workers,
+            config.pvp_pool.job_channel_capacity,
+        );
+        Workers {
+            normal,
+            pvp,
+            receivers: vec![normal_r, pvp_r],
+            finishing,
+            running,
+        }
+    }
+
+    pub fn normal(&self) -> Sender<Job> {
+        self.normal.clone()
+    }
+
+    pub fn pvp(&self) -> Sender<Job> {
+        self.pvp.clone()
+    }
+
+    /// The pool for `request`.
+    pub fn sender(&self, request: &GameRequest) -> &Sender<Job> {
+        match request {
+            // Compiling is about as heavy as a player
+            GameRequest::NormalGame(_) | GameRequest::Validation(_) => &self.normal,
+            GameRequest::PvPGame(_) => &self.pvp,
+        }
+    }
+
+    /// Drops the jobs no worker has started, which puts them back, and waits for the games
+    /// being played until `deadline`. Returns once every sender handed out is dropped and the
+    /// workers are done or the deadline passed, whether they all finished.
+    pub fn finish(self, deadline: Instant) -> bool {
+        let Workers {
+            normal,
+            pvp,
+            receivers,
+            finishing,
+            running,
+        } = self;
+        // Before draining, or a worker could start a job the drains were about to put back
+        finishing.store(true, Ordering::Release);
+        drop((normal, pvp));
+        let drains: Vec<_> = receivers
+            .into_iter()
+            .map(|r| {
+                thread::spawn(move || {
+                    for job in r.iter() {
+                        log::info!("Putting back game {}", job.request.game_id());
+                    }
+                })
+            })
+            .collect();
+        for drain in drains {
+            let _ = drain.join();
+        }
+
+        crossbeam_channel::select! {
+            recv(running) -> _ => true,
+            recv(crossbeam_channel::at(deadline)) -> _ => false,
+        }
+    }
+}
+
+/// Plays the jobs received on `jobs` one after another, publishing their statuses and
+/// settling their tickets, until every sender is gone.
+pub fn play(jobs: Jobs, config: Arc<DriverConfig>) {
+    while let Some(Job {
+        request,
+        ticket,
+        sink: publisher,
+    }) = jobs.next()
+    {
+        let game_id = request.game_id().to_owned();
+        if let Err(e) = publisher.publish(create_executing_response(&game_id)) {
+            log::error!("Unable to publish the executing status of {game_id}: {e:?}");
+        }
+        let report = Report::new(game_id.to_owned(), Arc::clone(&publisher));
+        let response = request.handle_reporting(&config, &report);
+        if ticket.is_abandoned() {
+            log::warn!("Game {game_id} finished after it was put back, dropping its result");
+            continue;
+        }
+        if let Some(SimulatorError::GameAlreadyRunning(e)) = &response.failure {
+            // Publishing would overwrite the statuses of the game being played
+            log::warn!("Dropping a duplicate request: {e}");
+            ticket.ack();
+            continue;
+        }
+        let failure = response
+            .failure
+            .clone()
+            .filter(SimulatorError::is_infrastructure);
+        if let Some(err) = &failure {
+            if ticket.can_retry() && ticket.attempt() < config.max_attempts {
+                log::warn!(
+                    "Game {game_id} failed on attempt {}, retrying: {err:?}",
+                    ticket.attempt()
+                );
+                ticket.retry(format!("{err:?}"));
+                continue;
+            }
+        }
+        let outcome = match failure {
+            Some(err) => {
+                log::error!("Game {game_id} kept failing, giving up: {err:?}");
+                Outcome::DeadLetter(format!("{err:?}"))
+            }
+            None => Outcome::Ack,
+        };
+        // Only a delivered result settles the request, otherwise it is played again
+        if let Err((e, ticket)) = publisher.publish_result(response, ticket, outcome) {
+            log::error!("Unable to publish the result of {game_id}: {e:?}");
+            // At the end of the queue, rather than right away while the broker is struggling
+            if ticket.attempt() < config.max_attempts {
+                ticket.retry(format!("{e:?}"));
+            } else {
+                ticket.dead_letter(format!("{e:?}"));
+            }
+        }
+    }
+}